@@ -0,0 +1,75 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use chip_8_rust::components::constants::INSTRUCTIONS_PER_FRAME;
+use chip_8_rust::components::cpu::Cpu;
+use chip_8_rust::components::recompiler::Engine;
+
+mod mixes;
+use mixes::MIXES;
+
+// instructions run per measured iteration, and frames for the frame benchmarks
+const INSTRUCTIONS: u64 = 10_000;
+const FRAMES: u64 = 60;
+
+const ROMS: [&str; 3] = ["invaders.c8", "pong2.c8", "tetris.c8"];
+
+fn rom(name: &str) -> Vec<u8> {
+    let path = format!("{}/programs/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Couldn't read {}: {}", path, e))
+}
+
+fn cpu(program: &[u8]) -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.load_bytes(program).expect("Failed to load rom");
+    cpu
+}
+
+// instructions per second through the interpreter's decoder, with no frames or timers
+fn instructions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instructions");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+
+    let roms = ROMS.iter().map(|&name| (name, rom(name)));
+    let mixes = MIXES
+        .iter()
+        .map(|&(name, program)| (name, program.to_vec()));
+    for (name, program) in roms.chain(mixes) {
+        let mut cpu = cpu(&program);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for _ in 0..INSTRUCTIONS {
+                    cpu.opcode();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+// frames per second of the bundled roms, run the way the main loop runs them, with each
+// execution engine
+fn frames(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frames");
+    group.throughput(Throughput::Elements(FRAMES));
+
+    for name in ROMS {
+        let program = rom(name);
+        for engine in [Engine::Interpreter, Engine::Blocks] {
+            let mut cpu = cpu(&program);
+            cpu.set_engine(engine);
+            let id = BenchmarkId::new(format!("{:?}", engine).to_lowercase(), name);
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    for _ in 0..FRAMES {
+                        cpu.run(INSTRUCTIONS_PER_FRAME);
+                        cpu.tick_timers();
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, instructions, frames);
+criterion_main!(benches);
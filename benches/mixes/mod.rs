@@ -0,0 +1,35 @@
+// small loops, each leaning on one part of the interpreter, shared by the benchmarks and
+// the lockstep tests
+pub const MIXES: [(&str, &[u8]); 4] = [
+    // register loads and the 8XYN arithmetic
+    (
+        "alu",
+        &[
+            0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x80, 0x14, 0x81, 0x25, 0x82, 0x06, 0x83, 0x0E,
+            0x82, 0x31, 0x83, 0x42, 0x80, 0x13, 0x70, 0x01, 0x71, 0x05, 0x12, 0x00,
+        ],
+    ),
+    // sprites drawn across the screen, wrapping and colliding
+    (
+        "draw",
+        &[
+            0xA2, 0x0C, 0xD0, 0x15, 0x70, 0x07, 0x71, 0x03, 0xD0, 0x18, 0x12, 0x02, 0xF0, 0x90,
+            0xF0, 0x90, 0x90, 0xFF, 0x81, 0xFF,
+        ],
+    ),
+    // BCD, register stores and loads
+    (
+        "memory",
+        &[
+            0xA3, 0x00, 0xF0, 0x33, 0xF2, 0x65, 0xF2, 0x55, 0x70, 0x0D, 0x12, 0x00,
+        ],
+    ),
+    // calls, returns, jumps and skips
+    (
+        "branch",
+        &[
+            0x22, 0x0E, 0x30, 0x00, 0x40, 0x00, 0x50, 0x10, 0x90, 0x10, 0x12, 0x00, 0x12, 0x00,
+            0x70, 0x01, 0x00, 0xEE,
+        ],
+    ),
+];
@@ -1,18 +1,186 @@
 use sdl2::audio::AudioCallback;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
 
-pub struct Sound {
-    pub data: Vec<u8>,
+use super::constants::FRAME_RATE;
+
+pub const SAMPLE_RATE: i32 = 44_100;
+
+// ramp lengths that keep the buzzer from clicking when it is gated on and off
+const ATTACK_MS: f32 = 2.0;
+const RELEASE_MS: f32 = 5.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sine" => Some(Waveform::Sine),
+            _ => None,
+        }
+    }
+}
+
+pub struct Tone {
+    pub waveform: Waveform,
+    pub frequency: f32,
     pub volume: f32,
-    pub pos: usize,
+    pub gate: bool,
+    // XO-CHIP 128-bit sample and its rate in bits per second, played instead of the waveform
+    pub pattern: Option<([u8; 16], f32)>,
+
+    sample_rate: f32,
+    phase: f32,
+    level: f32,
+}
+
+impl Tone {
+    pub fn new(waveform: Waveform, frequency: f32, volume: f32, sample_rate: i32) -> Self {
+        Self {
+            waveform,
+            frequency,
+            volume,
+            gate: false,
+            pattern: None,
+
+            sample_rate: sample_rate as f32,
+            phase: 0.0,
+            level: 0.0,
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        // move the envelope towards the gate, one step per sample
+        if self.gate {
+            self.level = (self.level + 1000.0 / (ATTACK_MS * self.sample_rate)).min(1.0);
+        } else {
+            self.level = (self.level - 1000.0 / (RELEASE_MS * self.sample_rate)).max(0.0);
+        }
+
+        // restart the wave while silent so every beep starts the same way
+        if self.level == 0.0 {
+            self.phase = 0.0;
+            return 0.0;
+        }
+
+        if let Some((pattern, rate)) = self.pattern {
+            let bit = (self.phase * 128.0) as usize;
+            let sample = if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                1.0
+            } else {
+                -1.0
+            };
+            self.phase = (self.phase + rate / 128.0 / self.sample_rate).fract();
+            return sample * self.volume * self.level;
+        }
+
+        let sample = match self.waveform {
+            Waveform::Square => {
+                if self.phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 4.0 * (self.phase - 0.5).abs() - 1.0,
+            Waveform::Sine => (2.0 * PI * self.phase).sin(),
+        };
+
+        self.phase = (self.phase + self.frequency / self.sample_rate).fract();
+
+        sample * self.volume * self.level
+    }
+}
+
+pub struct Sound {
+    pub tone: Tone,
 }
 
 impl AudioCallback for Sound {
-    type Channel = u8;
+    type Channel = f32;
 
-    fn callback(&mut self, out: &mut [u8]) {
+    fn callback(&mut self, out: &mut [f32]) {
         for dst in out.iter_mut() {
-            *dst = (*self.data.get(self.pos).unwrap_or(&0) as f32 * self.volume) as u8;
-            self.pos += 1;
+            *dst = self.tone.next_sample();
         }
     }
 }
+
+// records the buzzer to a 16-bit mono WAV file, one emulated frame at a time, so the
+// recording follows emulated time rather than the host audio device
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    tone: Tone,
+    sample_rate: u32,
+    frames: u64,
+    samples: u64,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create(filename: &str, tone: Tone, sample_rate: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(filename)?), tone, sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, tone: Tone, sample_rate: u32) -> io::Result<Self> {
+        // sizes are left at 0 until finish() knows them
+        out.write_all(b"RIFF")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(b"WAVE")?;
+        out.write_all(b"fmt ")?;
+        out.write_all(&16u32.to_le_bytes())?; // chunk size
+        out.write_all(&1u16.to_le_bytes())?; // pcm
+        out.write_all(&1u16.to_le_bytes())?; // mono
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * 2).to_le_bytes())?; // bytes per second
+        out.write_all(&2u16.to_le_bytes())?; // bytes per sample
+        out.write_all(&16u16.to_le_bytes())?; // bits per sample
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            out,
+            tone,
+            sample_rate,
+            frames: 0,
+            samples: 0,
+        })
+    }
+
+    // writes one 60 Hz frame of audio with the buzzer gated on or off
+    pub fn write_frame(&mut self, gate: bool, pattern: Option<([u8; 16], f32)>) -> io::Result<()> {
+        self.tone.gate = gate;
+        self.tone.pattern = pattern;
+        self.frames += 1;
+
+        let end = self.frames * self.sample_rate as u64 / FRAME_RATE as u64;
+        while self.samples < end {
+            let sample = (self.tone.next_sample().clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.out.write_all(&sample.to_le_bytes())?;
+            self.samples += 1;
+        }
+
+        Ok(())
+    }
+
+    // patches the chunk sizes into the header
+    pub fn finish(mut self) -> io::Result<W> {
+        let data_size = (self.samples * 2) as u32;
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + data_size).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&data_size.to_le_bytes())?;
+        self.out.flush()?;
+
+        Ok(self.out)
+    }
+}
@@ -0,0 +1,109 @@
+use serde::Deserialize;
+
+use super::octo;
+use super::palette::{parse_color, Palette};
+use super::platform::{QuirkOverrides, Wrap};
+
+// an Octo "cartridge": a GIF whose pixels carry the program's source and run options in the
+// low 2 bits of their palette indices, 4 pixels to a byte, behind a 32-bit length
+pub struct Cartridge {
+    pub program: Vec<u8>,
+    pub options: CartridgeOptions,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    program: String,
+    #[serde(default)]
+    options: CartridgeOptions,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CartridgeOptions {
+    pub tickrate: Option<u32>,
+    pub background_color: Option<String>,
+    pub fill_color: Option<String>,
+    pub fill_color2: Option<String>,
+    pub blend_color: Option<String>,
+    pub shift_quirks: Option<bool>,
+    pub load_store_quirks: Option<bool>,
+    pub jump_quirks: Option<bool>,
+    pub logic_quirks: Option<bool>,
+    // Octo capitalises the B
+    #[serde(rename = "vBlankQuirks", alias = "vblankQuirks")]
+    pub vblank_quirks: Option<bool>,
+    // sprites are clipped at the screen's edges rather than wrapped
+    pub clip_quirks: Option<bool>,
+}
+
+impl Cartridge {
+    pub fn is_cartridge(data: &[u8]) -> bool {
+        data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(data).map_err(|e| e.to_string())?;
+
+        let mut bits = vec![];
+        while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+            bits.extend(frame.buffer.iter().map(|index| index & 0x3));
+        }
+        let bytes: Vec<u8> = bits
+            .chunks_exact(4)
+            .map(|b| b[0] << 6 | b[1] << 4 | b[2] << 2 | b[3])
+            .collect();
+
+        if bytes.len() < 4 {
+            return Err("no cartridge data in the image".to_string());
+        }
+        let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let json = bytes
+            .get(4..4 + size)
+            .ok_or("cartridge data is cut short")?;
+        let payload: Payload = serde_json::from_slice(json).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            program: octo::assemble(&payload.program)?,
+            options: payload.options,
+        })
+    }
+}
+
+impl CartridgeOptions {
+    pub fn quirks(&self) -> QuirkOverrides {
+        QuirkOverrides {
+            shift: self.shift_quirks,
+            memory_leave_i_unchanged: self.load_store_quirks,
+            jump: self.jump_quirks,
+            logic: self.logic_quirks,
+            vblank: self.vblank_quirks,
+            wrap: self.clip_quirks.map(|clip| Wrap { x: !clip, y: !clip }),
+            ..QuirkOverrides::default()
+        }
+    }
+
+    // Octo's background, fill, second plane and overlap colors
+    pub fn palette(&self, name: &str) -> Result<Option<Palette>, String> {
+        let colors = [
+            &self.background_color,
+            &self.fill_color,
+            &self.fill_color2,
+            &self.blend_color,
+        ];
+        if colors.iter().any(|color| color.is_none()) {
+            return Ok(None);
+        }
+
+        let mut palette = Palette {
+            name: name.to_string(),
+            colors: [[0; 3]; 4],
+        };
+        for (dst, color) in palette.colors.iter_mut().zip(colors.iter()) {
+            *dst = parse_color(color.as_deref().unwrap_or(""))?;
+        }
+        Ok(Some(palette))
+    }
+}
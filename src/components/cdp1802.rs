@@ -0,0 +1,308 @@
+// the RCA CDP1802, the processor of the COSMAC VIP: sixteen 16-bit registers, any of which
+// can be the program counter (P) or the data pointer (X), and an 8-bit accumulator D
+
+// memory and the I/O lines the processor reaches through its pins
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    // OUT 1-7 puts a byte from memory on the data bus for device `port`
+    fn output(&mut self, port: u8, value: u8);
+    // INP 1-7 reads a byte from device `port`
+    fn input(&mut self, port: u8) -> u8;
+    // the external flag inputs EF1-EF4, numbered 1 to 4
+    fn flag(&mut self, flag: u8) -> bool;
+}
+
+#[derive(Clone)]
+pub struct Cdp1802 {
+    pub r: [u16; 16],
+    pub p: u8,
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    // X and P saved by an interrupt or MARK
+    pub t: u8,
+    pub ie: bool,
+    pub q: bool,
+    // stopped by IDL until an interrupt or DMA
+    pub idle: bool,
+}
+
+impl Default for Cdp1802 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cdp1802 {
+    // the state after a reset: running from R0 with interrupts enabled
+    pub fn new() -> Self {
+        Self {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let pc = self.r[self.p as usize];
+        self.r[self.p as usize] = pc.wrapping_add(1);
+        bus.read(pc)
+    }
+
+    fn rx(&self) -> u16 {
+        self.r[self.x as usize]
+    }
+
+    // D = a + b + carry, DF set on carry out
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // D = a - b - borrow, DF cleared on borrow
+    fn subtract(&mut self, a: u8, b: u8, borrow: bool) {
+        let difference = a as i16 - b as i16 - borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+
+    // the one-byte short branches: the low byte of the program counter is replaced
+    fn short_branch<B: Bus>(&mut self, bus: &mut B, taken: bool) {
+        let pc = self.r[self.p as usize];
+        if taken {
+            let [page, _] = pc.to_be_bytes();
+            self.r[self.p as usize] = u16::from_be_bytes([page, bus.read(pc)]);
+        } else {
+            self.r[self.p as usize] = pc.wrapping_add(1);
+        }
+    }
+
+    fn long_branch<B: Bus>(&mut self, bus: &mut B, taken: bool) {
+        let pc = self.r[self.p as usize];
+        if taken {
+            let target = (bus.read(pc) as u16) << 8 | bus.read(pc.wrapping_add(1)) as u16;
+            self.r[self.p as usize] = target;
+        } else {
+            self.r[self.p as usize] = pc.wrapping_add(2);
+        }
+    }
+
+    fn long_skip(&mut self, taken: bool) {
+        if taken {
+            self.r[self.p as usize] = self.r[self.p as usize].wrapping_add(2);
+        }
+    }
+
+    // executes one instruction and returns the machine cycles (8 clocks each) it took
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> u32 {
+        if self.idle {
+            return 2;
+        }
+
+        let opcode = self.fetch(bus);
+        let n = (opcode & 0x0F) as usize;
+        match opcode >> 4 {
+            // IDL / LDN: D = M(RN)
+            0x0 if n == 0 => self.idle = true,
+            0x0 => self.d = bus.read(self.r[n]),
+            // INC, DEC
+            0x1 => self.r[n] = self.r[n].wrapping_add(1),
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1),
+            // short branches on Q, D == 0, DF and the four flags; 38 (SKP) never branches
+            0x3 => {
+                let condition = match n & 0x7 {
+                    0 => true,
+                    1 => self.q,
+                    2 => self.d == 0,
+                    3 => self.df,
+                    flag => bus.flag(flag as u8 - 3),
+                };
+                let taken = if n == 0x8 {
+                    false
+                } else {
+                    condition != (n & 0x8 != 0)
+                };
+                self.short_branch(bus, taken);
+            }
+            // LDA: D = M(RN), RN + 1
+            0x4 => {
+                self.d = bus.read(self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            }
+            // STR: M(RN) = D
+            0x5 => bus.write(self.r[n], self.d),
+            0x6 => match n {
+                // IRX
+                0x0 => self.r[self.x as usize] = self.rx().wrapping_add(1),
+                // OUT 1-7: M(RX) to the bus, RX + 1
+                0x1..=0x7 => {
+                    let value = bus.read(self.rx());
+                    bus.output(n as u8, value);
+                    self.r[self.x as usize] = self.rx().wrapping_add(1);
+                }
+                // 68 is unused on the 1802
+                0x8 => {}
+                // INP 1-7: M(RX) = D = the bus
+                _ => {
+                    self.d = bus.input(n as u8 - 8);
+                    bus.write(self.rx(), self.d);
+                }
+            },
+            0x7 => match n {
+                // RET and DIS: restore X and P from M(RX), setting or clearing IE
+                0x0 | 0x1 => {
+                    let value = bus.read(self.rx());
+                    self.r[self.x as usize] = self.rx().wrapping_add(1);
+                    self.x = value >> 4;
+                    self.p = value & 0x0F;
+                    self.ie = n == 0;
+                }
+                // LDXA
+                0x2 => {
+                    self.d = bus.read(self.rx());
+                    self.r[self.x as usize] = self.rx().wrapping_add(1);
+                }
+                // STXD
+                0x3 => {
+                    bus.write(self.rx(), self.d);
+                    self.r[self.x as usize] = self.rx().wrapping_sub(1);
+                }
+                // ADC, SDB, SMB with M(RX); ADCI, SDBI, SMBI with the next byte
+                0x4 | 0x5 | 0x7 | 0xC | 0xD | 0xF => {
+                    let value = if n < 0x8 {
+                        bus.read(self.rx())
+                    } else {
+                        self.fetch(bus)
+                    };
+                    match n & 0x7 {
+                        0x4 => self.add(value, self.d, self.df),
+                        0x5 => self.subtract(value, self.d, !self.df),
+                        _ => self.subtract(self.d, value, !self.df),
+                    }
+                }
+                // SHRC: rotate D right through DF
+                0x6 => {
+                    let carry = self.d & 0x01 != 0;
+                    self.d = self.d >> 1 | (self.df as u8) << 7;
+                    self.df = carry;
+                }
+                // SAV: M(RX) = T
+                0x8 => bus.write(self.rx(), self.t),
+                // MARK: T = X,P; M(R2) = T; X = P; R2 - 1
+                0x9 => {
+                    self.t = self.x << 4 | self.p;
+                    bus.write(self.r[2], self.t);
+                    self.x = self.p;
+                    self.r[2] = self.r[2].wrapping_sub(1);
+                }
+                // REQ, SEQ
+                0xA => self.q = false,
+                0xB => self.q = true,
+                // SHLC: rotate D left through DF
+                _ => {
+                    let carry = self.d & 0x80 != 0;
+                    self.d = self.d << 1 | self.df as u8;
+                    self.df = carry;
+                }
+            },
+            // GLO, GHI, PLO, PHI
+            0x8 => self.d = self.r[n] as u8,
+            0x9 => self.d = (self.r[n] >> 8) as u8,
+            0xA => self.r[n] = self.r[n] & 0xFF00 | self.d as u16,
+            0xB => self.r[n] = self.r[n] & 0x00FF | (self.d as u16) << 8,
+            // long branches and skips, three machine cycles each
+            0xC => {
+                match n {
+                    // NOP
+                    0x4 => {}
+                    // LSNQ, LSNZ, LSNF, LSKP
+                    0x5 => self.long_skip(!self.q),
+                    0x6 => self.long_skip(self.d != 0),
+                    0x7 => self.long_skip(!self.df),
+                    0x8 => self.long_skip(true),
+                    // LSIE, LSQ, LSZ, LSDF
+                    0xC => self.long_skip(self.ie),
+                    0xD => self.long_skip(self.q),
+                    0xE => self.long_skip(self.d == 0),
+                    0xF => self.long_skip(self.df),
+                    // LBR, LBQ, LBZ, LBDF and their opposites
+                    _ => {
+                        let condition = match n & 0x3 {
+                            0 => true,
+                            1 => self.q,
+                            2 => self.d == 0,
+                            _ => self.df,
+                        };
+                        self.long_branch(bus, condition != (n & 0x8 != 0));
+                    }
+                }
+                return 3;
+            }
+            // SEP, SEX
+            0xD => self.p = n as u8,
+            0xE => self.x = n as u8,
+            _ => match n {
+                // LDI and the immediate forms of the ALU ops below
+                0x8 => self.d = self.fetch(bus),
+                // SHR, SHL
+                0x6 | 0xE => {
+                    if n == 0x6 {
+                        self.df = self.d & 0x01 != 0;
+                        self.d >>= 1;
+                    } else {
+                        self.df = self.d & 0x80 != 0;
+                        self.d <<= 1;
+                    }
+                }
+                // LDX, OR, AND, XOR, ADD, SD, SM and ORI, ANI, XRI, ADI, SDI, SMI
+                _ => {
+                    let value = if n < 0x8 {
+                        bus.read(self.rx())
+                    } else {
+                        self.fetch(bus)
+                    };
+                    match n & 0x7 {
+                        0x0 => self.d = value,
+                        0x1 => self.d |= value,
+                        0x2 => self.d &= value,
+                        0x3 => self.d ^= value,
+                        0x4 => self.add(value, self.d, false),
+                        0x5 => self.subtract(value, self.d, false),
+                        _ => self.subtract(self.d, value, false),
+                    }
+                }
+            },
+        }
+        2
+    }
+
+    // an interrupt request, taken only while IE is set: X and P go to T, and R1 runs with
+    // R2 as the data pointer
+    pub fn interrupt(&mut self) -> u32 {
+        if !self.ie {
+            return 0;
+        }
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+        1
+    }
+
+    // a DMA output cycle: the byte at R0 goes to the device asking for it
+    pub fn dma_out<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+}
@@ -0,0 +1,292 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::font::Font;
+use super::platform::QuirkOverrides;
+use super::recompiler::Engine;
+use super::timing::Timing;
+use super::vip::Cdp1802Mode;
+
+// config file picked up from the current directory when no --config is given
+pub const DEFAULT_CONFIG: &str = "chip8.toml";
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub keys: KeyConfig,
+    pub controller: ControllerConfig,
+    pub audio: AudioConfig,
+    pub display: DisplayConfig,
+    pub screenshot: ScreenshotConfig,
+    pub recording: RecordingConfig,
+    pub emulation: EmulationConfig,
+
+    // per-rom overrides, keyed by rom file name or sha1 (e.g. [roms."pong2.c8".keys])
+    pub roms: HashMap<String, RomConfig>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct RomConfig {
+    pub keys: KeyConfig,
+    pub controller: ControllerConfig,
+    pub emulation: EmulationConfig,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct EmulationConfig {
+    // platform id from the database (e.g. originalChip8, superchip), overriding the rom's
+    pub platform: Option<String>,
+    pub instructions_per_frame: Option<u32>,
+    // "vip" charges instructions their COSMAC VIP cycle costs instead of running
+    // instructions_per_frame of them
+    pub timing: Option<Timing>,
+    // "blocks" runs cached blocks of pre-decoded instructions, "lockstep" checks them against
+    // the interpreter as they run
+    pub engine: Option<Engine>,
+    // "routines" runs 0NNN machine code on an emulated CDP1802 (the default for hybridVIP),
+    // "interpreter" runs vip_interpreter on it instead of this interpreter
+    pub cdp1802: Option<Cdp1802Mode>,
+    // images of the VIP's monitor ROM and of a CHIP-8 interpreter for it
+    pub vip_monitor: Option<String>,
+    pub vip_interpreter: Option<String>,
+    // where the rom is loaded and starts running (default 0x200)
+    pub load_address: Option<u16>,
+    // the hex digit font: vip, chip48, dream6800, eti660 or octo (the default), and where it
+    // sits in memory (default 0x000, some interpreters use 0x050)
+    pub font: Option<Font>,
+    pub font_address: Option<u16>,
+    // directory with a full sha1-hashes.json, programs.json and platforms.json
+    pub database: Option<String>,
+    // individual quirks on top of the platform's
+    pub quirks: QuirkOverrides,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct KeyConfig {
+    // preset layout: qwerty, azerty, qwertz, dvorak or colemak
+    pub layout: Option<String>,
+    // map by physical key position instead of by the character on the key
+    pub scancodes: Option<bool>,
+    // CHIP-8 key (0-F) to one or more host key names, overriding the preset
+    #[serde(flatten)]
+    pub bindings: HashMap<String, HostKeys>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ControllerConfig {
+    // fraction of the stick range around the centre that is ignored
+    pub dead_zone: Option<f32>,
+    // fraction of the remaining range at which an axis counts as pressed
+    pub threshold: Option<f32>,
+    // SDL button or axis name ("dpup", "a", "leftx-", "lefttrigger") to CHIP-8 key (0-F)
+    #[serde(flatten)]
+    pub bindings: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    // square, triangle or sine
+    pub waveform: String,
+    // tone pitch in Hz
+    pub frequency: f32,
+    // 0.0 (silent) to 1.0
+    pub volume: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            waveform: "square".to_string(),
+            frequency: 440.0,
+            volume: 0.25,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DisplayConfig {
+    // built-in theme name or palette file
+    pub palette: Option<String>,
+    // RRGGBB overrides for the starting palette
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    // extra palette files to cycle through at runtime
+    pub palettes: Vec<String>,
+    // frames a pixel takes to fade out after switching off, 0 to turn it off instantly
+    pub persistence: u8,
+    // initial window size in host pixels per CHIP-8 pixel
+    pub scale: Option<u32>,
+    // only scale by whole multiples, leaving a border instead of uneven pixels
+    pub integer_scaling: bool,
+    pub fullscreen: bool,
+    // post-processing: scanlines, grid, bloom and smooth (scale2x)
+    pub filters: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ScreenshotConfig {
+    // where screenshots are saved, created if missing
+    pub directory: String,
+    // host pixels per CHIP-8 pixel, 1 for native resolution
+    pub scale: usize,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        Self {
+            directory: "screenshots".to_string(),
+            scale: 1,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    // where F9 saves recordings, created if missing
+    pub directory: String,
+    // gif or y4m
+    pub format: String,
+    // host pixels per CHIP-8 pixel
+    pub scale: usize,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            directory: "recordings".to_string(),
+            format: "gif".to_string(),
+            scale: 4,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum HostKeys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HostKeys {
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            HostKeys::One(name) => vec![name.clone()],
+            HostKeys::Many(names) => names.clone(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(filename)
+            .map_err(|e| format!("Couldn't read config {}: {}", filename, e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid config {}: {}", filename, e))
+    }
+
+    // loads the given config file, falling back to DEFAULT_CONFIG if it exists
+    pub fn load_or_default(filename: Option<&str>) -> Result<Self, String> {
+        match filename {
+            Some(filename) => Self::load(filename),
+            None if Path::new(DEFAULT_CONFIG).exists() => Self::load(DEFAULT_CONFIG),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn rom(&self, rom: &str, sha1: &str) -> Option<&RomConfig> {
+        let name = Path::new(rom)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(rom);
+        self.roms
+            .get(rom)
+            .or_else(|| self.roms.get(name))
+            .or_else(|| self.roms.get(sha1))
+    }
+
+    // key settings for a rom: the global [keys] table with the rom's overrides applied
+    pub fn keys_for(&self, rom: &str, sha1: &str) -> KeyConfig {
+        match self.rom(rom, sha1) {
+            Some(rom) => self.keys.merge(&rom.keys),
+            None => self.keys.clone(),
+        }
+    }
+
+    pub fn controller_for(&self, rom: &str, sha1: &str) -> ControllerConfig {
+        match self.rom(rom, sha1) {
+            Some(rom) => self.controller.merge(&rom.controller),
+            None => self.controller.clone(),
+        }
+    }
+
+    pub fn emulation_for(&self, rom: &str, sha1: &str) -> EmulationConfig {
+        match self.rom(rom, sha1) {
+            Some(rom) => self.emulation.merge(&rom.emulation),
+            None => self.emulation.clone(),
+        }
+    }
+}
+
+impl KeyConfig {
+    pub fn merge(&self, other: &KeyConfig) -> KeyConfig {
+        let mut bindings = self.bindings.clone();
+        for (key, hosts) in &other.bindings {
+            bindings.insert(key.clone(), hosts.clone());
+        }
+
+        KeyConfig {
+            layout: other.layout.clone().or_else(|| self.layout.clone()),
+            scancodes: other.scancodes.or(self.scancodes),
+            bindings,
+        }
+    }
+}
+
+impl ControllerConfig {
+    pub fn merge(&self, other: &ControllerConfig) -> ControllerConfig {
+        let mut bindings = self.bindings.clone();
+        for (control, key) in &other.bindings {
+            bindings.insert(control.clone(), key.clone());
+        }
+
+        ControllerConfig {
+            dead_zone: other.dead_zone.or(self.dead_zone),
+            threshold: other.threshold.or(self.threshold),
+            bindings,
+        }
+    }
+}
+
+impl EmulationConfig {
+    pub fn merge(&self, other: &EmulationConfig) -> EmulationConfig {
+        EmulationConfig {
+            platform: other.platform.clone().or_else(|| self.platform.clone()),
+            instructions_per_frame: other.instructions_per_frame.or(self.instructions_per_frame),
+            timing: other.timing.or(self.timing),
+            engine: other.engine.or(self.engine),
+            cdp1802: other.cdp1802.or(self.cdp1802),
+            vip_monitor: other
+                .vip_monitor
+                .clone()
+                .or_else(|| self.vip_monitor.clone()),
+            vip_interpreter: other
+                .vip_interpreter
+                .clone()
+                .or_else(|| self.vip_interpreter.clone()),
+            load_address: other.load_address.or(self.load_address),
+            font: other.font.or(self.font),
+            font_address: other.font_address.or(self.font_address),
+            database: other.database.clone().or_else(|| self.database.clone()),
+            quirks: self.quirks.merge(&other.quirks),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::config::ControllerConfig;
+
+// the d-pad and left stick sit on the WASD keys (5/7/8/9) of the default keyboard layout
+const DEFAULT_BINDINGS: [(&str, &str); 16] = [
+    ("dpup", "5"),
+    ("dpdown", "8"),
+    ("dpleft", "7"),
+    ("dpright", "9"),
+    ("leftx-", "7"),
+    ("leftx+", "9"),
+    ("lefty-", "5"),
+    ("lefty+", "8"),
+    ("a", "6"),
+    ("b", "4"),
+    ("x", "1"),
+    ("y", "2"),
+    ("leftshoulder", "C"),
+    ("rightshoulder", "D"),
+    ("back", "F"),
+    ("start", "E"),
+];
+
+// SDL's axis names, every other control is a button
+const AXES: [&str; 6] = [
+    "leftx",
+    "lefty",
+    "rightx",
+    "righty",
+    "lefttrigger",
+    "righttrigger",
+];
+
+const DEAD_ZONE: f32 = 0.2;
+const THRESHOLD: f32 = 0.5;
+
+struct AxisBinding {
+    axis: String,
+    positive: bool,
+    key: u8,
+    held: bool,
+}
+
+pub struct ControllerBindings {
+    buttons: Vec<(String, u8)>,
+    axes: Vec<AxisBinding>,
+    dead_zone: f32,
+    threshold: f32,
+}
+
+impl ControllerBindings {
+    pub fn from_config(config: &ControllerConfig) -> Result<Self, String> {
+        let mut bindings: Vec<(String, String)> = DEFAULT_BINDINGS
+            .iter()
+            .filter(|(control, _)| !config.bindings.contains_key(*control))
+            .map(|(control, key)| (control.to_string(), key.to_string()))
+            .collect();
+        bindings.extend(config.bindings.clone());
+
+        let mut buttons = vec![];
+        let mut axes = vec![];
+        for (control, key) in bindings {
+            // an empty key unbinds a default control
+            if key.is_empty() {
+                continue;
+            }
+            let key = u8::from_str_radix(&key, 16)
+                .ok()
+                .filter(|key| *key < 16)
+                .ok_or(format!("Unknown CHIP-8 key: {}", key))?;
+
+            // "leftx-" / "leftx+" pick a direction, triggers only move one way
+            let control = control.to_ascii_lowercase();
+            let (name, positive) = match control.strip_suffix('-') {
+                Some(name) => (name, false),
+                None => (control.strip_suffix('+').unwrap_or(&control), true),
+            };
+            if !AXES.contains(&name) {
+                if name != control {
+                    return Err(format!("Unknown controller input: {}", control));
+                }
+                buttons.push((control, key));
+                continue;
+            }
+            axes.push(AxisBinding {
+                axis: name.to_string(),
+                positive,
+                key,
+                held: false,
+            });
+        }
+
+        let dead_zone = config.dead_zone.unwrap_or(DEAD_ZONE);
+        let threshold = config.threshold.unwrap_or(THRESHOLD);
+        if !(0.0..1.0).contains(&dead_zone) || !(0.0..=1.0).contains(&threshold) {
+            return Err("Controller dead_zone and threshold must be between 0 and 1".to_string());
+        }
+
+        Ok(Self {
+            buttons,
+            axes,
+            dead_zone,
+            threshold,
+        })
+    }
+
+    // button names, for the frontend to check against the ones SDL knows
+    pub fn buttons(&self) -> impl Iterator<Item = &str> + '_ {
+        self.buttons.iter().map(|(button, _)| button.as_str())
+    }
+
+    pub fn button<'a>(&'a self, button: &'a str) -> impl Iterator<Item = u8> + 'a {
+        self.buttons
+            .iter()
+            .filter(move |(host, _)| host.eq_ignore_ascii_case(button))
+            .map(|(_, key)| *key)
+    }
+
+    // moves an axis and returns the keys whose pressed state changed
+    pub fn axis(&mut self, axis: &str, value: i16) -> Vec<(u8, u8)> {
+        // rescale so the edge of the dead zone reads as 0 and full deflection as 1
+        let deflection = (value as f32 / i16::MAX as f32).max(-1.0);
+        let magnitude = ((deflection.abs() - self.dead_zone) / (1.0 - self.dead_zone)).max(0.0);
+
+        let mut changed = vec![];
+        for binding in self
+            .axes
+            .iter_mut()
+            .filter(|binding| binding.axis.eq_ignore_ascii_case(axis))
+        {
+            let held = magnitude > 0.0
+                && magnitude >= self.threshold
+                && (deflection > 0.0) == binding.positive;
+            if held != binding.held {
+                binding.held = held;
+                changed.push((binding.key, held as u8));
+            }
+        }
+
+        changed
+    }
+}
+
+impl Default for ControllerBindings {
+    fn default() -> Self {
+        Self::from_config(&ControllerConfig::default()).unwrap()
+    }
+}
@@ -1,9 +1,20 @@
-use rand;
-use std::fs::File;
-use std::io::prelude::*;
-
-use super::constants::FONTSET;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io::Read;
+
+use super::cartridge::CartridgeOptions;
+use super::constants::{
+    HIRES_HEIGHT, HIRES_WIDTH, LOAD_ADDRESS, MEMORY_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+use super::font::Font;
+use super::framebuffer::Framebuffer;
 use super::keypad::Keypad;
+use super::loader::{self, LoadError, Rom};
+use super::platform::Quirks;
+use super::recompiler::{BlockCache, Engine, Op};
+use super::timing::{self, VIP_CYCLES_PER_FRAME, VIP_DISPLAY_CYCLES};
+use super::transpile::Translated;
+use super::vip::{self, Cdp1802Mode, Vip};
 
 pub struct Cpu {
     pc: u16,
@@ -13,44 +24,115 @@ pub struct Cpu {
 
     v: [u8; 16],
     stack: [u16; 16],
-    memory: [u8; 4096],
+    memory: Vec<u8>,
 
-    pub gfx: [u8; 2048],
+    pub gfx: Framebuffer,
+    // the low resolution size, 64x48 on the ETI-660
+    lores: (usize, usize),
+    // planes drawn to and cleared by the XO-CHIP FN01 instruction
+    planes: u8,
 
     delay_timer: u8,
     sound_timer: u8,
 
+    // SUPER-CHIP "RPL user flags" saved by FX75
+    flags: [u8; 16],
+    // XO-CHIP 1-bit sample played while the sound timer runs, and its pitch
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
+
     pub draw_flag: bool,
-    pub beep_flag: bool,
     pub keypad: Keypad,
+    pub quirks: Quirks,
+
+    // sha1 of the loaded rom, for looking it up in the database
+    pub rom_sha1: String,
+    load_address: u16,
+    rom_address: usize,
+    // where the rom file said it goes (Intel HEX)
+    rom_origin: Option<u16>,
+    // the 4x5 font, followed by the SUPER-CHIP 8x10 one
+    font: Font,
+    font_address: u16,
+    rom_size: usize,
+    // set by the 60 Hz frame boundary until the next instruction runs
+    vblank: bool,
+    // a DXYN is waiting for the next frame to draw (vblank quirk)
+    waiting_for_vblank: bool,
+    // machine cycles left in the current frame under VIP timing, negative when the last
+    // instruction ran over into the next frame
+    cycles: i32,
+    // run options of an Octo cartridge, when the rom came from one
+    pub cartridge: Option<CartridgeOptions>,
+    // an emulated COSMAC VIP, for 0NNN machine code routines or for running a VIP
+    // interpreter image instead of this interpreter
+    vip: Option<Vip>,
+
+    // CXNN's generator, cloned along with everything else for lockstep runs
+    rng: StdRng,
+    engine: Engine,
+    blocks: BlockCache,
+    // an interpreter run alongside the blocks, in lockstep mode
+    shadow: Option<Box<Cpu>>,
+    // the program's code translated ahead of time by the transpile command
+    translated: Option<Translated>,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cpu {
     pub fn new() -> Self {
         let mut cpu = Self {
-            pc: 0x200, // starts at 0x200
+            pc: LOAD_ADDRESS, // starts at 0x200
             opcode: 0,
             ir: 0,
             sp: 0,
 
             v: [0; 16],
             stack: [0; 16],
-            memory: [0; 4096],
+            memory: vec![0; MEMORY_SIZE],
 
-            gfx: [0; 2048],
+            gfx: Framebuffer::new(SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize),
+            lores: (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize),
+            planes: 1,
 
             delay_timer: 0,
             sound_timer: 0,
 
+            flags: [0; 16],
+            pattern: None,
+            pitch: 64,
+
             draw_flag: true,
-            beep_flag: false,
             keypad: Keypad::new(),
+            quirks: Quirks::default(),
+
+            rom_sha1: String::new(),
+            load_address: LOAD_ADDRESS,
+            rom_address: LOAD_ADDRESS as usize,
+            rom_origin: None,
+            font: Font::default(),
+            font_address: 0,
+            rom_size: 0,
+            vblank: false,
+            waiting_for_vblank: false,
+            cycles: 0,
+            cartridge: None,
+            vip: None,
+
+            rng: StdRng::from_entropy(),
+            engine: Engine::Interpreter,
+            blocks: BlockCache::default(),
+            shadow: None,
+            translated: None,
         };
 
         // load fontset
-        for i in 0..80 {
-            cpu.memory[i] = FONTSET[i];
-        }
+        cpu.load_font();
 
         return cpu;
     }
@@ -58,29 +140,66 @@ impl Cpu {
     pub fn opcode(&mut self) {
         // fetch opcode
         self.opcode = (self.memory[self.pc as usize] as u16) << 8
-            | (self.memory[(self.pc + 1) as usize] as u16);
+            | (self.memory[(self.pc as usize + 1) % MEMORY_SIZE] as u16);
+        let vblank = std::mem::take(&mut self.vblank);
 
         // For troubleshooting opcodes
         // println!("opcode: {:02X}{:02X}", (self.opcode >> 8) as u8, self.opcode as u8);
 
         // process opcode
         match self.opcode & 0xF000 {
+            // 0NNN: calls the 1802 machine code routine at NNN (COSMAC VIP); only 00E0 and
+            // 00EE are instructions there
+            0x0000 if self.vip.is_some() && self.opcode != 0x00E0 && self.opcode != 0x00EE => {
+                self.call_machine_code(self.opcode & 0x0FFF);
+            }
+
             0x0000 => {
-                match self.opcode & 0x000F {
+                match self.opcode & 0x00FF {
                     // 0x00E0: clears the screen
-                    0x0000 => {
-                        for i in 0..2048 {
-                            self.gfx[i] = 0;
-                        }
+                    0x00E0 => {
+                        self.gfx.clear(self.planes);
                         self.draw_flag = true;
                         self.pc += 2;
                     }
                     // 0x00EE: returns from subroutine
-                    0x000E => {
+                    0x00EE => {
                         self.sp -= 1; // 16 levels of stack, decrease stack pointer to prevent overwrite
                         self.pc = self.stack[self.sp as usize]; // put the stored return address from the stack back into the program counter
                         self.pc += 2 // don't forget to increase the program counter!
                     }
+                    // 0x00CN: scrolls the screen down N pixels (SUPER-CHIP)
+                    0x00C0..=0x00CF => {
+                        self.scroll(0, (self.opcode & 0x000F) as isize);
+                        self.pc += 2;
+                    }
+                    // 0x00DN: scrolls the screen up N pixels (XO-CHIP)
+                    0x00D0..=0x00DF => {
+                        self.scroll(0, -((self.opcode & 0x000F) as isize));
+                        self.pc += 2;
+                    }
+                    // 0x00FB: scrolls the screen right 4 pixels (SUPER-CHIP)
+                    0x00FB => {
+                        self.scroll(4, 0);
+                        self.pc += 2;
+                    }
+                    // 0x00FC: scrolls the screen left 4 pixels (SUPER-CHIP)
+                    0x00FC => {
+                        self.scroll(-4, 0);
+                        self.pc += 2;
+                    }
+                    // 0x00FD: exits the interpreter (SUPER-CHIP), here it just stops
+                    0x00FD => {}
+                    // 0x00FE: switches to 64x32 (SUPER-CHIP)
+                    0x00FE => {
+                        self.set_hires(false);
+                        self.pc += 2;
+                    }
+                    // 0x00FF: switches to 128x64 (SUPER-CHIP)
+                    0x00FF => {
+                        self.set_hires(true);
+                        self.pc += 2;
+                    }
                     _ => {
                         panic!("unknown opcode [0x0000]: 0x{:X}.", self.opcode);
                     }
@@ -102,7 +221,7 @@ impl Cpu {
             // 0x2NNN: calls subroutine at NNN.
             0x3000 => {
                 if self.v[((self.opcode & 0x0F00) >> 8) as usize] == (self.opcode & 0x00FF) as u8 {
-                    self.pc += 4;
+                    self.skip();
                 } else {
                     self.pc += 2;
                 }
@@ -111,20 +230,44 @@ impl Cpu {
             // 0x4XNN: skips the next instruction if VX doesn't equal NN
             0x4000 => {
                 if self.v[((self.opcode & 0x0F00) >> 8) as usize] != (self.opcode & 0x00FF) as u8 {
-                    self.pc += 4;
+                    self.skip();
                 } else {
                     self.pc += 2;
                 }
             }
 
-            // 0x5XY0: skips the next instruction if VX equals VY
             0x5000 => {
-                if self.v[((self.opcode & 0x0F00) >> 8) as usize]
-                    == self.v[((self.opcode & 0x00F0) >> 4) as usize]
-                {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
+                let x = ((self.opcode & 0x0F00) >> 8) as usize;
+                let y = ((self.opcode & 0x00F0) >> 4) as usize;
+                match self.opcode & 0x000F {
+                    // 0x5XY0: skips the next instruction if VX equals VY
+                    0x0000 => {
+                        if self.v[x] == self.v[y] {
+                            self.skip();
+                        } else {
+                            self.pc += 2;
+                        }
+                    }
+
+                    // 0x5XY2: stores VX to VY in memory starting at address ir, which is left alone (XO-CHIP)
+                    0x0002 => {
+                        for (i, reg) in Self::register_range(x, y).enumerate() {
+                            self.memory[(self.ir as usize + i) % MEMORY_SIZE] = self.v[reg];
+                        }
+                        self.pc += 2;
+                    }
+
+                    // 0x5XY3: fills VX to VY from memory starting at address ir (XO-CHIP)
+                    0x0003 => {
+                        for (i, reg) in Self::register_range(x, y).enumerate() {
+                            self.v[reg] = self.memory[(self.ir as usize + i) % MEMORY_SIZE];
+                        }
+                        self.pc += 2;
+                    }
+
+                    _ => {
+                        panic!("unknown opcode [0x5000]: 0x{:X}.", self.opcode);
+                    }
                 }
             }
 
@@ -154,6 +297,9 @@ impl Cpu {
                     0x0001 => {
                         self.v[((self.opcode & 0x0F00) >> 8) as usize] |=
                             self.v[((self.opcode & 0x00F0) >> 4) as usize];
+                        if self.quirks.logic {
+                            self.v[0xF] = 0;
+                        }
                         self.pc += 2;
                     }
 
@@ -161,6 +307,9 @@ impl Cpu {
                     0x0002 => {
                         self.v[((self.opcode & 0x0F00) >> 8) as usize] &=
                             self.v[((self.opcode & 0x00F0) >> 4) as usize];
+                        if self.quirks.logic {
+                            self.v[0xF] = 0;
+                        }
                         self.pc += 2;
                     }
 
@@ -168,62 +317,82 @@ impl Cpu {
                     0x0003 => {
                         self.v[((self.opcode & 0x0F00) >> 8) as usize] ^=
                             self.v[((self.opcode & 0x00F0) >> 4) as usize];
+                        if self.quirks.logic {
+                            self.v[0xF] = 0;
+                        }
                         self.pc += 2;
                     }
 
                     // 0x8XY4: adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't
+                    // VF is always written last, so it holds the flag even when it is VX
                     0x0004 => {
-                        if self.v[((self.opcode & 0x00F0) >> 4) as usize]
+                        let carry = if self.v[((self.opcode & 0x00F0) >> 4) as usize]
                             > (0xFF - self.v[((self.opcode & 0x0F00) >> 8) as usize])
                         {
-                            self.v[0xF] = 1; // carry
+                            1 // carry
                         } else {
-                            self.v[0xF] = 0;
-                        }
+                            0
+                        };
                         let pos: usize = ((self.opcode & 0x0F00) >> 8) as usize;
                         self.v[pos] = self.v[pos]
                             .wrapping_add(self.v[((self.opcode & 0x00F0) >> 4) as usize]);
+                        self.v[0xF] = carry;
                         self.pc += 2;
                     }
 
                     // 0x8XY5: VY is subtracted from VX. VF is set to 0 when there's a borrow, and 1 when there isn't
                     0x0005 => {
                         let pos: usize = ((self.opcode & 0x0F00) >> 8) as usize;
-                        if self.v[((self.opcode & 0x00F0) >> 4) as usize] > self.v[pos] {
-                            self.v[0xF] = 0; // there is a borrow
+                        let borrow = if self.v[((self.opcode & 0x00F0) >> 4) as usize] > self.v[pos]
+                        {
+                            0 // there is a borrow
                         } else {
-                            self.v[0xF] = 1;
-                        }
+                            1
+                        };
                         self.v[pos] = self.v[pos]
                             .wrapping_sub(self.v[((self.opcode & 0x00F0) >> 4) as usize]);
+                        self.v[0xF] = borrow;
                         self.pc += 2;
                     }
 
                     // 0x8XY6: shifts VX right by one. VF is set to the value of the least significant bit of VX before the shift
                     0x0006 => {
-                        self.v[0xF] = self.v[((self.opcode & 0x0F00) >> 8) as usize] & 0x1;
+                        // the original interpreter shifted VY into VX
+                        if !self.quirks.shift {
+                            self.v[((self.opcode & 0x0F00) >> 8) as usize] =
+                                self.v[((self.opcode & 0x00F0) >> 4) as usize];
+                        }
+                        let bit = self.v[((self.opcode & 0x0F00) >> 8) as usize] & 0x1;
                         self.v[((self.opcode & 0x0F00) >> 8) as usize] >>= 1;
+                        self.v[0xF] = bit;
                         self.pc += 2;
                     }
 
                     // 0x8XY7: sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't
                     0x0007 => {
                         let pos: usize = ((self.opcode & 0x0F00) >> 8) as usize;
-                        if self.v[pos] > self.v[((self.opcode & 0x00F0) >> 4) as usize] {
+                        let borrow = if self.v[pos] > self.v[((self.opcode & 0x00F0) >> 4) as usize]
+                        {
                             // VY-VX
-                            self.v[0xF] = 0; // there is a borrow
+                            0 // there is a borrow
                         } else {
-                            self.v[0xF] = 1;
-                        }
+                            1
+                        };
                         self.v[pos] = self.v[((self.opcode & 0x00F0) >> 4) as usize]
                             .wrapping_sub(self.v[pos]);
+                        self.v[0xF] = borrow;
                         self.pc += 2;
                     }
 
                     // 0x8XYE: shifts VX left by one. VF is set to the value of the most significant bit of VX before the shift
                     0x000E => {
-                        self.v[0xF] = self.v[((self.opcode & 0x0F00) >> 8) as usize] >> 7;
+                        if !self.quirks.shift {
+                            self.v[((self.opcode & 0x0F00) >> 8) as usize] =
+                                self.v[((self.opcode & 0x00F0) >> 4) as usize];
+                        }
+                        let bit = self.v[((self.opcode & 0x0F00) >> 8) as usize] >> 7;
                         self.v[((self.opcode & 0x0F00) >> 8) as usize] <<= 1;
+                        self.v[0xF] = bit;
                         self.pc += 2;
                     }
 
@@ -238,7 +407,7 @@ impl Cpu {
                 if self.v[((self.opcode & 0x0F00) >> 8) as usize]
                     != self.v[((self.opcode & 0x00F0) >> 4) as usize]
                 {
-                    self.pc += 4;
+                    self.skip();
                 } else {
                     self.pc += 2;
                 }
@@ -252,13 +421,19 @@ impl Cpu {
 
             // BNNN: jumps to the address NNN plus V0
             0xB000 => {
-                self.pc = (self.opcode & 0x0FFF).wrapping_add(self.v[0] as u16);
+                // CHIP-48 and SUPER-CHIP read it as BXNN, jumping to XNN plus VX
+                let offset = if self.quirks.jump {
+                    self.v[((self.opcode & 0x0F00) >> 8) as usize]
+                } else {
+                    self.v[0]
+                };
+                self.pc = (self.opcode & 0x0FFF).wrapping_add(offset as u16);
             }
 
             // CXNN: sets VX to a random number and NN
             0xC000 => {
                 self.v[((self.opcode & 0x0F00) >> 8) as usize] =
-                    rand::random::<u8>() & (self.opcode as u8);
+                    self.rng.gen::<u8>() & (self.opcode as u8);
                 self.pc += 2;
             }
 
@@ -266,24 +441,46 @@ impl Cpu {
             // each row of 8 pixels is read as bit-coded starting from memory location ri;
             // ri value doesn't change after the execution of this instruction.
             // VF is set to 1 if any screen pixels are flipped from set to unset when the sprite is drawn,
-            // and to 0 if that doesn't happen
+            // and to 0 if that doesn't happen.
+            // DXY0 draws a 16x16 sprite of two bytes per row (SUPER-CHIP)
             0xD000 => {
-                let x = self.v[((self.opcode & 0x0F00) >> 8) as usize] as u16;
-                let y = self.v[((self.opcode & 0x00F0) >> 4) as usize] as u16;
-                let height = self.opcode & 0x000F;
+                // the VIP only draws right after the display interrupt, so a sprite waits
+                // for the next frame unless that is what just happened
+                if self.quirks.vblank && !vblank {
+                    self.waiting_for_vblank = true;
+                    return;
+                }
 
+                let x = self.v[((self.opcode & 0x0F00) >> 8) as usize] as usize % self.gfx.width();
+                let y = self.v[((self.opcode & 0x00F0) >> 4) as usize] as usize % self.gfx.height();
+                let (columns, rows) = match self.opcode & 0x000F {
+                    0 => (16, 16),
+                    n => (8, n as usize),
+                };
+
+                // with both XO-CHIP planes selected, the second plane's rows follow the first's
+                let mut address = self.ir as usize;
                 self.v[0xF] = 0;
-                for yline in 0..height {
-                    let pixel = self.memory[(self.ir + yline) as usize] as u16;
-                    for xline in 0..8 {
-                        if (pixel & (0x80 >> xline)) != 0 {
-                            let pos = (x + xline + ((y + yline) * 64)) as usize;
-                            if pos < 2048 {
-                                if self.gfx[pos] == 1 {
-                                    self.v[0xF] = 1;
-                                }
-                                self.gfx[pos] ^= 1;
-                            }
+                for plane in [1, 2] {
+                    if self.planes & plane == 0 {
+                        continue;
+                    }
+                    for yline in 0..rows {
+                        let mut pixels = (self.memory[address % MEMORY_SIZE] as u16) << 8;
+                        if columns == 16 {
+                            pixels |= self.memory[(address + 1) % MEMORY_SIZE] as u16;
+                        }
+                        address += columns / 8;
+
+                        // sprites are clipped at the bottom and right edges, or wrap around
+                        // to the top and left
+                        let row = match y + yline {
+                            row if row < self.gfx.height() => row,
+                            row if self.quirks.wrap.y => row % self.gfx.height(),
+                            _ => continue,
+                        };
+                        if self.gfx.draw_row(plane, x, row, pixels, self.quirks.wrap.x) {
+                            self.v[0xF] = 1;
                         }
                     }
                 }
@@ -299,7 +496,7 @@ impl Cpu {
                         if self.keypad.key[self.v[((self.opcode & 0x0F00) >> 8) as usize] as usize]
                             != 0
                         {
-                            self.pc += 4;
+                            self.skip();
                         } else {
                             self.pc += 2;
                         }
@@ -310,7 +507,7 @@ impl Cpu {
                         if self.keypad.key[self.v[((self.opcode & 0x0F00) >> 8) as usize] as usize]
                             == 0
                         {
-                            self.pc += 4;
+                            self.skip();
                         } else {
                             self.pc += 2;
                         }
@@ -324,6 +521,29 @@ impl Cpu {
 
             0xF000 => {
                 match self.opcode & 0x00FF {
+                    // F000 NNNN: sets ir to the 16-bit address in the next two bytes (XO-CHIP)
+                    0x0000 => {
+                        self.ir = (self.memory[(self.pc as usize + 2) % MEMORY_SIZE] as u16) << 8
+                            | self.memory[(self.pc as usize + 3) % MEMORY_SIZE] as u16;
+                        self.pc += 4;
+                    }
+
+                    // FN01: selects the planes N that drawing and clearing affect (XO-CHIP)
+                    0x0001 => {
+                        self.planes = ((self.opcode & 0x0F00) >> 8) as u8 & 0x3;
+                        self.pc += 2;
+                    }
+
+                    // F002: loads the 16-byte audio pattern at ir (XO-CHIP)
+                    0x0002 => {
+                        let mut pattern = [0; 16];
+                        for (i, byte) in pattern.iter_mut().enumerate() {
+                            *byte = self.memory[(self.ir as usize + i) % MEMORY_SIZE];
+                        }
+                        self.pattern = Some(pattern);
+                        self.pc += 2;
+                    }
+
                     // FX07: sets VX to the value of the delay timer
                     0x0007 => {
                         self.v[((self.opcode & 0x0F00) >> 8) as usize] = self.delay_timer;
@@ -376,7 +596,21 @@ impl Cpu {
 
                     // FX29: sets ir to the location of the sprite for the character in VX. Characters 0-F (in hexadecimal) are represented by a 4x5 font
                     0x0029 => {
-                        self.ir = self.v[((self.opcode & 0x0F00) >> 8) as usize] as u16 * 0x5;
+                        self.ir = self.font_address
+                            + (self.v[((self.opcode & 0x0F00) >> 8) as usize] & 0xF) as u16 * 0x5;
+                        self.pc += 2;
+                    }
+
+                    // FX30: sets ir to the 8x10 big font digit in VX (SUPER-CHIP)
+                    0x0030 => {
+                        self.ir = self.big_font_address()
+                            + (self.v[((self.opcode & 0x0F00) >> 8) as usize] & 0xF) as u16 * 10;
+                        self.pc += 2;
+                    }
+
+                    // FX3A: sets the audio pattern's pitch to VX (XO-CHIP)
+                    0x003A => {
+                        self.pitch = self.v[((self.opcode & 0x0F00) >> 8) as usize];
                         self.pc += 2;
                     }
 
@@ -384,9 +618,9 @@ impl Cpu {
                     0x0033 => {
                         self.memory[self.ir as usize] =
                             self.v[((self.opcode & 0x0F00) >> 8) as usize] / 100;
-                        self.memory[(self.ir + 1) as usize] =
+                        self.memory[(self.ir as usize + 1) % MEMORY_SIZE] =
                             (self.v[((self.opcode & 0x0F00) >> 8) as usize] / 10) % 10;
-                        self.memory[(self.ir + 2) as usize] =
+                        self.memory[(self.ir as usize + 2) % MEMORY_SIZE] =
                             (self.v[((self.opcode & 0x0F00) >> 8) as usize] % 100) % 10;
                         self.pc += 2;
                     }
@@ -395,11 +629,12 @@ impl Cpu {
                     0x0055 => {
                         let j = (self.opcode & 0x0F00) >> 8;
                         for i in 0..j + 1 {
-                            self.memory[(self.ir + i) as usize] = self.v[i as usize];
+                            self.memory[(self.ir as usize + i as usize) % MEMORY_SIZE] =
+                                self.v[i as usize];
                         }
 
                         // on the original interpreter, when the operation is done, ir = ir + X + 1.
-                        self.ir = self.ir.wrapping_add(j + 1);
+                        self.increment_ir(j);
                         self.pc += 2;
                     }
 
@@ -407,11 +642,26 @@ impl Cpu {
                     0x0065 => {
                         let j = (self.opcode & 0x0F00) >> 8;
                         for i in 0..j + 1 {
-                            self.v[i as usize] = self.memory[(self.ir + i) as usize];
+                            self.v[i as usize] =
+                                self.memory[(self.ir as usize + i as usize) % MEMORY_SIZE];
                         }
 
                         // on the original interpreter, when the operation is done, ir = ir + X + 1.
-                        self.ir = self.ir.wrapping_add(j + 1);
+                        self.increment_ir(j);
+                        self.pc += 2;
+                    }
+
+                    // FX75: saves V0 to VX in the user flags (SUPER-CHIP)
+                    0x0075 => {
+                        let x = ((self.opcode & 0x0F00) >> 8) as usize;
+                        self.flags[..=x].copy_from_slice(&self.v[..=x]);
+                        self.pc += 2;
+                    }
+
+                    // FX85: restores V0 to VX from the user flags (SUPER-CHIP)
+                    0x0085 => {
+                        let x = ((self.opcode & 0x0F00) >> 8) as usize;
+                        self.v[..=x].copy_from_slice(&self.flags[..=x]);
                         self.pc += 2;
                     }
 
@@ -425,25 +675,541 @@ impl Cpu {
                 panic!("unknown opcode [0x0000]: 0x{:X}.", self.opcode);
             }
         }
+    }
+
+    // runs one frame's worth of instructions as the COSMAC VIP would, charging each its cycle
+    // cost; whatever the last one overran by comes out of the next frame
+    pub fn run_vip_frame(&mut self) {
+        self.cycles += VIP_CYCLES_PER_FRAME - VIP_DISPLAY_CYCLES;
+        while self.cycles > 0 {
+            let pc = self.pc;
+            let opcode = (self.memory[pc as usize] as u16) << 8
+                | self.memory[(pc as usize + 1) % MEMORY_SIZE] as u16;
+            let x = ((opcode & 0x0F00) >> 8) as usize;
+            let vx = if opcode & 0xF000 == 0xB000 && !self.quirks.jump {
+                self.v[0]
+            } else {
+                self.v[x]
+            };
+
+            self.step();
+            // the rest of the frame is spent waiting
+            if self.waiting_for_vblank {
+                self.cycles = 0;
+                break;
+            }
+
+            let skipped = matches!(opcode & 0xF000, 0x3000 | 0x4000 | 0x5000 | 0x9000 | 0xE000)
+                && self.pc != pc.wrapping_add(2);
+            self.cycles -= timing::vip_cycles(opcode, vx, skipped) as i32;
+        }
+    }
+
+    // runs the next instruction with the chosen engine
+    pub fn step(&mut self) {
+        match self.engine {
+            Engine::Interpreter => self.opcode(),
+            Engine::Blocks => self.run_op(),
+            Engine::Lockstep => {
+                let Some(mut shadow) = self.shadow.take() else {
+                    return self.run_op();
+                };
+                let pc = self.pc;
+                let opcode = (self.memory[pc as usize] as u16) << 8
+                    | self.memory[(pc as usize + 1) % MEMORY_SIZE] as u16;
+
+                shadow.keypad.key = self.keypad.key;
+                shadow.opcode();
+                self.run_op();
+                if let Some(difference) = self.differs_from(&shadow, false) {
+                    panic!(
+                        "lockstep: {} after 0x{:04X} at 0x{:03X}",
+                        difference, opcode, pc
+                    );
+                }
+                self.shadow = Some(shadow);
+            }
+        }
+    }
+
+    // runs translated code from now on wherever it covers pc; see transpile.rs
+    pub fn set_translated(&mut self, translated: Translated) {
+        self.translated = Some(translated);
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    // whether memory from `address` still holds `code`, which translated code checks before
+    // running in case the program overwrote it
+    pub fn code_matches(&self, address: u16, code: &[u8]) -> bool {
+        self.memory
+            .get(address as usize..address as usize + code.len())
+            == Some(code)
+    }
+
+    // switches engines; lockstep starts its interpreter from the current state
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        self.blocks.clear();
+        self.shadow = None;
+        if engine == Engine::Lockstep {
+            self.shadow = Some(Box::new(self.interpreter_copy()));
+        }
+    }
+
+    // runs up to `count` instructions, stopping early once the program waits for the next
+    // frame; the block engine and translated code run whole blocks at a time
+    pub fn run(&mut self, count: u32) {
+        let mut done = 0;
+        while done < count && !self.waiting_for_vblank {
+            if let Some(translated) = self.translated {
+                // nothing translated at pc: a computed jump or code that was overwritten
+                done += match translated(self, count - done) {
+                    0 => {
+                        self.opcode();
+                        1
+                    }
+                    ran => ran,
+                };
+            } else if self.engine == Engine::Blocks {
+                done += self.run_block(count - done);
+            } else {
+                self.step();
+                done += 1;
+            }
+        }
+    }
+
+    // runs the block at pc, or its first `limit` instructions, returning how many ran; it
+    // stops at the first op that leaves pc anywhere but the next instruction
+    fn run_block(&mut self, limit: u32) -> u32 {
+        let block = self.blocks.block(self.pc, &self.memory);
+        let mut ran = 0;
+        for index in block.first..block.first + block.length.min(limit as usize) {
+            let pc = self.pc;
+            self.execute(self.blocks.op(index));
+            ran += 1;
+            if self.pc != pc.wrapping_add(2) || self.waiting_for_vblank {
+                break;
+            }
+        }
+        ran
+    }
+
+    // runs the instruction at pc from the block cache
+    fn run_op(&mut self) {
+        let op = self.blocks.next(self.pc, &self.memory);
+        self.execute(op);
+    }
+
+    // runs one micro-op, handing Interpret and Store to the interpreter; inlined, as it is
+    // run_block's whole loop and all translated code does
+    #[inline(always)]
+    pub fn execute(&mut self, op: Op) {
+        if !matches!(op, Op::Interpret | Op::Store) {
+            self.vblank = false;
+        }
+
+        match op {
+            Op::Load(x, nn) => self.v[x] = nn,
+            Op::Add(x, nn) => self.v[x] = self.v[x].wrapping_add(nn),
+            Op::Copy(x, y) => self.v[x] = self.v[y],
+            Op::Or(x, y) | Op::And(x, y) | Op::Xor(x, y) => {
+                match op {
+                    Op::Or(..) => self.v[x] |= self.v[y],
+                    Op::And(..) => self.v[x] &= self.v[y],
+                    _ => self.v[x] ^= self.v[y],
+                }
+                if self.quirks.logic {
+                    self.v[0xF] = 0;
+                }
+            }
+            Op::AddV(x, y) => {
+                let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
+                self.v[x] = sum;
+                self.v[0xF] = carry as u8;
+            }
+            Op::Sub(x, y) => {
+                let (difference, borrow) = self.v[x].overflowing_sub(self.v[y]);
+                self.v[x] = difference;
+                self.v[0xF] = !borrow as u8;
+            }
+            Op::SubN(x, y) => {
+                let (difference, borrow) = self.v[y].overflowing_sub(self.v[x]);
+                self.v[x] = difference;
+                self.v[0xF] = !borrow as u8;
+            }
+            Op::ShiftRight(x, y) | Op::ShiftLeft(x, y) => {
+                if !self.quirks.shift {
+                    self.v[x] = self.v[y];
+                }
+                let bit = if let Op::ShiftRight(..) = op {
+                    let bit = self.v[x] & 0x1;
+                    self.v[x] >>= 1;
+                    bit
+                } else {
+                    let bit = self.v[x] >> 7;
+                    self.v[x] <<= 1;
+                    bit
+                };
+                self.v[0xF] = bit;
+            }
+            Op::SetI(address) => self.ir = address,
+            Op::AddI(x) => {
+                let sum = self.ir.wrapping_add(self.v[x] as u16);
+                self.v[0xF] = (sum > 0xFFF) as u8;
+                self.ir = sum;
+            }
+            Op::Random(x, nn) => self.v[x] = self.rng.gen::<u8>() & nn,
+            Op::Font(x) => self.ir = self.font_address + (self.v[x] & 0xF) as u16 * 0x5,
+            Op::GetDelay(x) => self.v[x] = self.delay_timer,
+            Op::SetDelay(x) => self.delay_timer = self.v[x],
+            Op::SetSound(x) => self.sound_timer = self.v[x],
+            Op::Jump(address) => {
+                self.pc = address;
+                return;
+            }
+            Op::Call(address) => {
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                self.pc = address;
+                return;
+            }
+            Op::Return => {
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
+            }
+            Op::SkipEq(x, nn) | Op::SkipNe(x, nn) => {
+                if (self.v[x] == nn) == matches!(op, Op::SkipEq(..)) {
+                    self.skip();
+                    return;
+                }
+            }
+            Op::SkipEqV(x, y) | Op::SkipNeV(x, y) => {
+                if (self.v[x] == self.v[y]) == matches!(op, Op::SkipEqV(..)) {
+                    self.skip();
+                    return;
+                }
+            }
+            Op::SkipKey(x) | Op::SkipNoKey(x) => {
+                let pressed = self.keypad.key[self.v[x] as usize] != 0;
+                if pressed == matches!(op, Op::SkipKey(..)) {
+                    self.skip();
+                    return;
+                }
+            }
+            Op::Interpret => {
+                self.opcode();
+                return;
+            }
+            Op::Store => {
+                let written = self.write_range();
+                self.opcode();
+                if let Some((start, end)) = written {
+                    self.blocks.invalidate(start, end);
+                    // stores past the end of memory carry on at the start
+                    if end > MEMORY_SIZE {
+                        self.blocks.invalidate(0, end - MEMORY_SIZE);
+                    }
+                }
+                return;
+            }
+        }
+        self.pc += 2;
+    }
+
+    // the memory the instruction at pc is about to write to, which may hold cached code
+    fn write_range(&self) -> Option<(usize, usize)> {
+        let opcode = (self.memory[self.pc as usize] as u16) << 8
+            | self.memory[(self.pc as usize + 1) % MEMORY_SIZE] as u16;
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let start = self.ir as usize;
+        match opcode & 0xF000 {
+            // a VIP machine code routine could write anywhere
+            0x0000 if self.vip.is_some() => Some((0, MEMORY_SIZE)),
+            0x5000 if opcode & 0x000F == 0x2 => Some((start, start + x.max(y) - x.min(y) + 1)),
+            0xF000 if opcode & 0x00FF == 0x33 => Some((start, start + 3)),
+            0xF000 if opcode & 0x00FF == 0x55 => Some((start, start + x + 1)),
+            _ => None,
+        }
+    }
+
+    // a plain interpreter in the same state, for lockstep runs
+    fn interpreter_copy(&self) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.pc = self.pc;
+        cpu.ir = self.ir;
+        cpu.sp = self.sp;
+        cpu.v = self.v;
+        cpu.stack = self.stack;
+        cpu.memory = self.memory.clone();
+        cpu.gfx = self.gfx.clone();
+        cpu.lores = self.lores;
+        cpu.planes = self.planes;
+        cpu.delay_timer = self.delay_timer;
+        cpu.sound_timer = self.sound_timer;
+        cpu.flags = self.flags;
+        cpu.pattern = self.pattern;
+        cpu.pitch = self.pitch;
+        cpu.quirks = self.quirks;
+        cpu.load_address = self.load_address;
+        cpu.rom_address = self.rom_address;
+        cpu.rom_origin = self.rom_origin;
+        cpu.font = self.font;
+        cpu.font_address = self.font_address;
+        cpu.rom_size = self.rom_size;
+        cpu.vblank = self.vblank;
+        cpu.waiting_for_vblank = self.waiting_for_vblank;
+        cpu.vip = self.vip.clone();
+        cpu.rng = self.rng.clone();
+        cpu
+    }
+
+    // what differs between two machines, if anything; memory and the screen only when `full`,
+    // as comparing them after every instruction would be slow
+    fn differs_from(&self, other: &Cpu, full: bool) -> Option<String> {
+        let registers = [
+            ("pc", self.pc == other.pc),
+            ("I", self.ir == other.ir),
+            ("V registers", self.v == other.v),
+            ("stack", self.sp == other.sp && self.stack == other.stack),
+            ("delay timer", self.delay_timer == other.delay_timer),
+            ("sound timer", self.sound_timer == other.sound_timer),
+            ("flags", self.flags == other.flags),
+            ("planes", self.planes == other.planes),
+            (
+                "audio",
+                self.pattern == other.pattern && self.pitch == other.pitch,
+            ),
+            (
+                "vblank wait",
+                self.waiting_for_vblank == other.waiting_for_vblank,
+            ),
+        ];
+        let full_state = [
+            ("memory", self.memory == other.memory),
+            ("screen", self.gfx == other.gfx),
+        ];
+        registers
+            .iter()
+            .chain(full_state.iter().filter(|_| full))
+            .find(|(_, same)| !same)
+            .map(|(name, _)| format!("{} differs from the interpreter's", name))
+    }
+
+    // hands the machine to a 1802 routine the way the VIP interpreter does: V0-VF, the display
+    // and the stack in the top page of RAM, I in RA, the timers in R8 and the CHIP-8 program
+    // counter in R5, all read back once it returns
+    fn call_machine_code(&mut self, address: u16) {
+        let Some(mut vip) = self.vip.take() else {
+            return;
+        };
+
+        let registers = vip::REGISTERS as usize;
+        self.memory[registers..registers + 16].copy_from_slice(&self.v);
+        let display = vip::DISPLAY as usize;
+        for (y, row) in self.memory[display..display + 256]
+            .chunks_mut(8)
+            .enumerate()
+        {
+            let pixels = if y < self.gfx.height() {
+                (self.gfx.row(0, y) >> 64) as u64
+            } else {
+                0
+            };
+            row.copy_from_slice(&pixels.to_be_bytes());
+        }
+
+        let cpu = &mut vip.cpu;
+        cpu.r[2] = vip::STACK_TOP - 2 * self.sp;
+        cpu.r[3] = address;
+        cpu.r[5] = self.pc + 2;
+        cpu.r[6] = vip::REGISTERS + ((self.opcode & 0x0F00) >> 8);
+        cpu.r[7] = vip::REGISTERS + ((self.opcode & 0x00F0) >> 4);
+        cpu.r[8] = (self.delay_timer as u16) << 8 | self.sound_timer as u16;
+        cpu.r[9] = self.rng.gen::<u16>();
+        cpu.r[0xA] = self.ir;
+        cpu.r[0xB] = vip::DISPLAY;
+        cpu.p = 3;
+        cpu.x = 2;
+        cpu.ie = false;
+        cpu.idle = false;
+
+        if let Err(e) = vip.call(&mut self.memory, &self.keypad.key) {
+            panic!("{} [0x0000]: 0x{:X}.", e, self.opcode);
+        }
+
+        let cpu = &vip.cpu;
+        self.v
+            .copy_from_slice(&self.memory[registers..registers + 16]);
+        self.ir = cpu.r[0xA];
+        self.delay_timer = (cpu.r[8] >> 8) as u8;
+        self.sound_timer = cpu.r[8] as u8;
+        self.pc = cpu.r[5];
+        for (y, row) in self.memory[display..display + 256].chunks(8).enumerate() {
+            if y < self.gfx.height() {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(row);
+                let pixels = u64::from_be_bytes(bytes);
+                let rest = self.gfx.row(0, y) & !(!0 << 64);
+                self.gfx.set_row(0, y, (pixels as u128) << 64 | rest);
+            }
+        }
+        self.draw_flag = true;
+        self.vip = Some(vip);
+    }
+
+    // attaches an emulated VIP; in interpreter mode `interpreter` is the image it boots, copied
+    // to 0x000 below the program
+    pub fn set_vip(&mut self, mut vip: Vip, interpreter: &[u8]) {
+        if vip.mode == Cdp1802Mode::Interpreter {
+            self.memory[..interpreter.len()].copy_from_slice(interpreter);
+            vip.reset();
+        }
+        self.vip = Some(vip);
+    }
+
+    // whether a VIP interpreter image runs the program instead of this interpreter
+    pub fn runs_vip_interpreter(&self) -> bool {
+        matches!(&self.vip, Some(vip) if vip.mode == Cdp1802Mode::Interpreter)
+    }
+
+    // runs one frame of the emulated VIP and shows what its video chip displayed
+    pub fn run_vip_interpreter_frame(&mut self) {
+        let Some(mut vip) = self.vip.take() else {
+            return;
+        };
+        vip.run_frame(&mut self.memory, &self.keypad.key);
+
+        let (height, rows) = vip.screen();
+        if self.lores != (64, height) {
+            self.set_screen_size(64, height);
+        }
+        for (y, row) in rows.iter().enumerate() {
+            self.gfx
+                .set_row(0, y, (u64::from_be_bytes(*row) as u128) << 64);
+        }
+        self.draw_flag = true;
+        self.vip = Some(vip);
+    }
+
+    // skips the next instruction, which is four bytes long if it is XO-CHIP's F000 NNNN
+    fn skip(&mut self) {
+        let next = (self.memory[(self.pc as usize + 2) % MEMORY_SIZE] as u16) << 8
+            | self.memory[(self.pc as usize + 3) % MEMORY_SIZE] as u16;
+        self.pc += if next == 0xF000 { 6 } else { 4 };
+    }
+
+    // registers X to Y for 5XY2/5XY3, backwards when X is above Y
+    fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    // switching resolution clears the screen
+    fn set_hires(&mut self, hires: bool) {
+        let (width, height) = if hires {
+            (HIRES_WIDTH as usize, HIRES_HEIGHT as usize)
+        } else {
+            self.lores
+        };
+        self.gfx = Framebuffer::new(width, height);
+        self.draw_flag = true;
+    }
+
+    // moves the selected planes by (dx, dy) pixels, filling in with blank pixels
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        self.gfx.scroll(self.planes, dx, dy);
+        self.draw_flag = true;
+    }
+
+    // where FX55/FX65 leave ir after touching V0 to VX
+    fn increment_ir(&mut self, x: u16) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        let step = if self.quirks.memory_increment_by_x {
+            x
+        } else {
+            x + 1
+        };
+        self.ir = self.ir.wrapping_add(step);
+    }
+
+    // counts both timers down, called once per 60 Hz frame; this is also the vertical blank a
+    // DXYN may be waiting for
+    pub fn tick_timers(&mut self) {
+        self.vblank = true;
+        self.waiting_for_vblank = false;
 
-        // update timers
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
 
         if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                self.beep_flag = true;
-            }
             self.sound_timer -= 1;
         }
+
+        if let Some(mut shadow) = self.shadow.take() {
+            shadow.tick_timers();
+            if let Some(difference) = self.differs_from(&shadow, true) {
+                panic!("lockstep: {} at the end of the frame", difference);
+            }
+            self.shadow = Some(shadow);
+        }
+    }
+
+    // whether the program can't go on until the next frame, which ends the current one
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    // size of the emulated screen in pixels
+    pub fn resolution(&self) -> (usize, usize) {
+        (self.gfx.width(), self.gfx.height())
+    }
+
+    // the buzzer sounds for as long as the sound timer is non-zero, or on a VIP while Q is set
+    pub fn sound_active(&self) -> bool {
+        match &self.vip {
+            Some(vip) if vip.mode == Cdp1802Mode::Interpreter => vip.sound_active(),
+            _ => self.sound_timer > 0,
+        }
+    }
+
+    // the XO-CHIP audio pattern, once a program has loaded one, and its playback rate in
+    // bits per second
+    pub fn audio_pattern(&self) -> Option<([u8; 16], f32)> {
+        let rate = 4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0);
+        self.pattern.map(|pattern| (pattern, rate))
+    }
+
+    // where the loaded rom starts in memory
+    pub fn program_address(&self) -> usize {
+        self.rom_address
+    }
+
+    // the address the rom file asked to be loaded at, if it named one
+    pub fn rom_origin(&self) -> Option<u16> {
+        self.rom_origin
+    }
+
+    // the loaded rom as it sits in memory
+    pub fn program(&self) -> &[u8] {
+        &self.memory[self.rom_address..self.rom_address + self.rom_size]
     }
 
     /*pub fn debug_render(& self) {
         // draw
         for y in 0..32 {
             for x in 0..64 {
-                if self.gfx[((y * 64) + x) as usize] == 0 {
+                if self.gfx.pixel(x, y) == 0 {
                     print!("O");
                 } else {
                     print!(" ");
@@ -454,27 +1220,134 @@ impl Cpu {
         println!("");
     }*/
 
-    pub fn load_application(&mut self, filename: &str) -> bool {
-        // open file
-        let mut file = File::open(filename).expect("Error occurred when opening file");
+    // where programs are copied to and start running, 0x200 unless set before loading
+    pub fn set_load_address(&mut self, address: u16) {
+        self.load_address = address;
+        self.pc = address;
+    }
 
-        // get file size
-        let fsize = file.metadata().unwrap().len();
+    // moves an already loaded program, for when its platform turns out to load elsewhere
+    pub fn relocate(&mut self, address: u16) -> Result<(), LoadError> {
+        let rom = self.program().to_vec();
+        let capacity = MEMORY_SIZE.saturating_sub(address as usize);
+        if rom.len() > capacity {
+            return Err(LoadError::TooBig {
+                size: rom.len(),
+                capacity,
+            });
+        }
 
-        // read the file to a buffer
-        let mut buffer = vec![];
-        file.read_to_end(&mut buffer).expect("Couldn't read file");
-        drop(file);
+        if self.covers_font(address as usize, rom.len()) {
+            return Err(LoadError::OverFont(self.font_address));
+        }
 
-        // copy the buffer to chip8 memory
-        if (4096 - 512) > fsize {
-            for i in 0..fsize {
-                self.memory[(i + 512) as usize] = buffer[i as usize];
-            }
-        } else {
-            panic!("ROM too big for memory");
+        for byte in &mut self.memory[self.rom_address..self.rom_address + rom.len()] {
+            *byte = 0;
+        }
+        self.memory[address as usize..address as usize + rom.len()].copy_from_slice(&rom);
+        self.rom_address = address as usize;
+        self.set_load_address(address);
+        self.blocks.clear();
+        Ok(())
+    }
+
+    // swaps the fonts for another interpreter's and puts them at `address`, copying them into
+    // memory for FX29 and FX30 to point at
+    pub fn set_font(&mut self, font: Font, address: u16) -> Result<(), String> {
+        let fonts = address as usize..address as usize + font.size();
+        if fonts.end > MEMORY_SIZE {
+            return Err(format!(
+                "Font address 0x{:X} is too high: the font takes {} bytes",
+                address,
+                font.size()
+            ));
         }
+        let program = self.rom_address..self.rom_address + self.rom_size;
+        if !program.is_empty() && fonts.start < program.end && program.start < fonts.end {
+            return Err(format!(
+                "Font address 0x{:X} puts the font over the program at 0x{:X}",
+                address, self.rom_address
+            ));
+        }
+        self.font = font;
+        self.font_address = address;
+        self.load_font();
+        Ok(())
+    }
+
+    // a program placed at `address` would overwrite the fonts
+    fn covers_font(&self, address: usize, size: usize) -> bool {
+        let font = self.font_address as usize;
+        address < font + self.font.size() && font < address + size
+    }
+
+    fn load_font(&mut self) {
+        let small = self.font_address as usize;
+        let digits = self.font.digits();
+        self.memory[small..small + digits.len()].copy_from_slice(digits);
+        let big = self.big_font_address() as usize;
+        let big_digits = self.font.big_digits();
+        self.memory[big..big + big_digits.len()].copy_from_slice(big_digits);
+    }
+
+    fn big_font_address(&self) -> u16 {
+        self.font_address + self.font.digits().len() as u16
+    }
+
+    // the low resolution screen size, which programs start in; clears the screen
+    pub fn set_screen_size(&mut self, width: usize, height: usize) {
+        self.lores = (width, height);
+        self.set_hires(false);
+    }
+
+    // loads a rom file, or stdin for "-", in any format the loader recognizes
+    pub fn load_application(&mut self, filename: &str) -> Result<(), LoadError> {
+        let rom = loader::read_file(filename)?;
+        self.load_rom(rom)
+    }
+
+    pub fn load_reader<R: Read>(&mut self, reader: R) -> Result<(), LoadError> {
+        let rom = loader::decode(loader::read_all(reader)?, false)?;
+        self.load_rom(rom)
+    }
+
+    // a plain binary, copied into memory as it is
+    pub fn load_bytes(&mut self, data: &[u8]) -> Result<(), LoadError> {
+        self.load_rom(Rom {
+            data: data.to_vec(),
+            address: None,
+            cartridge: None,
+        })
+    }
+
+    fn load_rom(&mut self, rom: Rom) -> Result<(), LoadError> {
+        let address = rom.address.unwrap_or(self.load_address) as usize;
+        let capacity = MEMORY_SIZE.saturating_sub(address);
+        if rom.data.len() > capacity {
+            return Err(LoadError::TooBig {
+                size: rom.data.len(),
+                capacity,
+            });
+        }
+
+        if self.covers_font(address, rom.data.len()) {
+            return Err(LoadError::OverFont(self.font_address));
+        }
+
+        // a file that says where it goes starts running there too
+        if let Some(origin) = rom.address {
+            self.set_load_address(origin);
+        }
+
+        // copy the rom to chip8 memory
+        self.memory[address..address + rom.data.len()].copy_from_slice(&rom.data);
+        self.rom_sha1 = sha1_smol::Sha1::from(&rom.data).digest().to_string();
+        self.rom_address = address;
+        self.rom_origin = rom.address;
+        self.rom_size = rom.data.len();
+        self.cartridge = rom.cartridge;
+        self.blocks.clear();
 
-        return true;
+        Ok(())
     }
 }
@@ -0,0 +1,166 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use super::font::Font;
+use super::palette::Palette;
+use super::platform::{Platform, QuirkOverrides, Quirks};
+
+// a small database in the chip-8-community format (sha1-hashes.json, programs.json and
+// platforms.json) ships inside the binary; a full copy can be loaded over it
+const BUNDLED_HASHES: &str = include_str!("../../database/sha1-hashes.json");
+const BUNDLED_PROGRAMS: &str = include_str!("../../database/programs.json");
+const BUNDLED_PLATFORMS: &str = include_str!("../../database/platforms.json");
+
+#[derive(Deserialize)]
+pub struct Program {
+    pub title: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Rom {
+    pub platforms: Vec<String>,
+    pub quirky_platforms: HashMap<String, QuirkOverrides>,
+    pub tickrate: Option<u32>,
+    pub colors: Option<Colors>,
+    // what the game uses each CHIP-8 key for, e.g. "left": 7
+    pub keys: BTreeMap<String, u8>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Colors {
+    pub pixels: Vec<String>,
+}
+
+// how a rom should be run, as far as the database knows
+pub struct Profile {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub platform: Option<String>,
+    pub quirks: Quirks,
+    pub instructions_per_frame: Option<u32>,
+    pub palette: Option<Palette>,
+    pub keys: BTreeMap<String, u8>,
+    pub load_address: Option<u16>,
+    pub font: Option<Font>,
+    pub font_address: Option<u16>,
+    pub resolution: Option<(usize, usize)>,
+}
+
+pub struct Database {
+    hashes: HashMap<String, usize>,
+    programs: Vec<Program>,
+    platforms: Vec<Platform>,
+}
+
+impl Database {
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_HASHES, BUNDLED_PROGRAMS, BUNDLED_PLATFORMS)
+            .expect("bundled database is invalid")
+    }
+
+    // reads a directory holding the three database files
+    pub fn load(directory: &str) -> Result<Self, String> {
+        let read = |name: &str| {
+            let filename = Path::new(directory).join(name);
+            fs::read_to_string(&filename)
+                .map_err(|e| format!("Couldn't read {}: {}", filename.display(), e))
+        };
+
+        Self::parse(
+            &read("sha1-hashes.json")?,
+            &read("programs.json")?,
+            &read("platforms.json")?,
+        )
+        .map_err(|e| format!("Invalid database in {}: {}", directory, e))
+    }
+
+    fn parse(hashes: &str, programs: &str, platforms: &str) -> Result<Self, String> {
+        Ok(Self {
+            hashes: serde_json::from_str(hashes).map_err(|e| e.to_string())?,
+            programs: serde_json::from_str(programs).map_err(|e| e.to_string())?,
+            platforms: serde_json::from_str(platforms).map_err(|e| e.to_string())?,
+        })
+    }
+
+    // adds another database on top of this one, its entries winning over ours
+    pub fn merge(&mut self, other: Database) {
+        let offset = self.programs.len();
+        for (hash, index) in other.hashes {
+            self.hashes.insert(hash, index + offset);
+        }
+        self.programs.extend(other.programs);
+
+        for platform in other.platforms {
+            self.platforms.retain(|known| known.id != platform.id);
+            self.platforms.push(platform);
+        }
+    }
+
+    pub fn platform(&self, id: &str) -> Option<&Platform> {
+        self.platforms.iter().find(|platform| platform.id == id)
+    }
+
+    // looks a rom up by its sha1; `platform` forces a platform instead of the rom's first one
+    pub fn profile(&self, sha1: &str, platform: Option<&str>) -> Result<Profile, String> {
+        let program = self
+            .hashes
+            .get(sha1)
+            .and_then(|index| self.programs.get(*index));
+        let rom = program.and_then(|program| program.roms.get(sha1));
+
+        let mut profile = Profile {
+            title: program.map(|program| program.title.clone()),
+            authors: program
+                .map(|program| program.authors.clone())
+                .unwrap_or_default(),
+            platform: None,
+            quirks: Quirks::default(),
+            instructions_per_frame: rom.and_then(|rom| rom.tickrate),
+            palette: None,
+            keys: rom.map(|rom| rom.keys.clone()).unwrap_or_default(),
+            load_address: None,
+            font: None,
+            font_address: None,
+            resolution: None,
+        };
+
+        let platform = platform.or_else(|| {
+            rom.and_then(|rom| rom.platforms.first())
+                .map(|id| id.as_str())
+        });
+        if let Some(id) = platform {
+            let platform = self
+                .platform(id)
+                .ok_or(format!("Unknown platform: {}", id))?;
+            profile.platform = Some(platform.id.clone());
+            profile.quirks = platform.quirks;
+            profile.instructions_per_frame =
+                profile.instructions_per_frame.or(platform.default_tickrate);
+            profile.load_address = platform.load_address;
+            profile.font = platform.font;
+            profile.font_address = platform.font_address;
+            profile.resolution = platform.resolution();
+
+            if let Some(overrides) = rom.and_then(|rom| rom.quirky_platforms.get(id)) {
+                profile.quirks.apply(overrides);
+            }
+        }
+
+        if let Some(colors) = rom.and_then(|rom| rom.colors.as_ref()) {
+            if !colors.pixels.is_empty() {
+                let name = profile.title.clone().unwrap_or_default();
+                profile.palette = Some(Palette::parse(&name, &colors.pixels.join(" "))?);
+            }
+        }
+
+        Ok(profile)
+    }
+}
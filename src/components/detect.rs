@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+
+use super::platform::QuirkOverrides;
+
+const MOVES_I: &str =
+    "FX55/FX65 are followed by more memory access from where they left I, so I has to move";
+
+// a best guess at how to run a rom the database doesn't know, from the code it can reach
+pub struct Detection {
+    pub platform: &'static str,
+    pub quirks: QuirkOverrides,
+    pub reasons: Vec<String>,
+}
+
+// instructions only SUPER-CHIP (and XO-CHIP after it) understand
+fn is_superchip(opcode: u16) -> bool {
+    matches!(opcode, 0x00FB..=0x00FF)
+        || opcode & 0xFFF0 == 0x00C0
+        || (opcode & 0xF000 == 0xD000 && opcode & 0x000F == 0)
+        || matches!(opcode & 0xF0FF, 0xF030 | 0xF075 | 0xF085)
+}
+
+fn is_xochip(opcode: u16) -> bool {
+    opcode == 0xF000
+        || opcode == 0xF002
+        || opcode & 0xFFF0 == 0x00D0
+        || matches!(opcode & 0xF00F, 0x5002 | 0x5003)
+        || matches!(opcode & 0xF0FF, 0xF001 | 0xF03A)
+}
+
+pub fn detect(rom: &[u8]) -> Detection {
+    let mut reasons = vec![];
+
+    let eti660 = looks_like_eti660(rom);
+    let load_address = if eti660 {
+        reasons.push(
+            "every jump and call lands at 0x600 or above, as if assembled for the ETI-660"
+                .to_string(),
+        );
+        0x600
+    } else {
+        0x200
+    };
+
+    let reachable = reachable(rom, load_address);
+    let opcode = |address: u16| {
+        let i = (address - load_address) as usize;
+        (rom[i] as u16) << 8 | *rom.get(i + 1).unwrap_or(&0) as u16
+    };
+
+    let mut superchip = vec![];
+    let mut xochip = vec![];
+    for &address in &reachable {
+        let op = opcode(address);
+        if is_xochip(op) {
+            xochip.push(op);
+        } else if is_superchip(op) {
+            superchip.push(op);
+        }
+    }
+    let examples = |opcodes: &mut Vec<u16>| {
+        opcodes.sort_unstable();
+        opcodes.dedup();
+        opcodes
+            .iter()
+            .take(4)
+            .map(|op| format!("{:04X}", op))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    // 8XY6/8XYE: programs that shift VX in place leave Y at 0, the original shifts VY into VX
+    let (mut in_place, mut from_y) = (0, 0);
+    for &address in &reachable {
+        let op = opcode(address);
+        if op & 0xF000 == 0x8000 && matches!(op & 0x000F, 0x6 | 0xE) {
+            let x = (op & 0x0F00) >> 8;
+            let y = (op & 0x00F0) >> 4;
+            if x != y {
+                if y == 0 {
+                    in_place += 1;
+                } else {
+                    from_y += 1;
+                }
+            }
+        }
+    }
+
+    // FX55/FX65 followed by another use of I before it is set again counts on I moving along
+    let relies_on_increment = reachable.iter().any(|&address| {
+        let op = opcode(address);
+        matches!(op & 0xF0FF, 0xF055 | 0xF065) && uses_i_next(rom, load_address, address)
+    });
+
+    let mut quirks = QuirkOverrides::default();
+    let platform = if !xochip.is_empty() {
+        reasons.push(format!("XO-CHIP instructions: {}", examples(&mut xochip)));
+        "xochip"
+    } else if !superchip.is_empty() {
+        reasons.push(format!(
+            "SUPER-CHIP instructions: {}",
+            examples(&mut superchip)
+        ));
+        if relies_on_increment {
+            reasons.push(format!("{}, as SUPER-CHIP 1.0 did", MOVES_I));
+            "superchip1"
+        } else {
+            "superchip"
+        }
+    } else if eti660 {
+        "eti660"
+    } else {
+        reasons.push("only CHIP-8 instructions".to_string());
+        if relies_on_increment {
+            reasons.push(format!("{}, as the original interpreter did", MOVES_I));
+        }
+        if from_y > in_place || relies_on_increment {
+            "originalChip8"
+        } else {
+            "modernChip8"
+        }
+    };
+
+    if from_y > in_place {
+        reasons.push(format!(
+            "{} shifts use VY, so VY is shifted into VX",
+            from_y
+        ));
+        quirks.shift = Some(false);
+    } else if in_place > 0 {
+        reasons.push(format!(
+            "{} shifts leave Y at 0, so VX is shifted in place",
+            in_place
+        ));
+        quirks.shift = Some(true);
+    }
+
+    Detection {
+        platform,
+        quirks,
+        reasons,
+    }
+}
+
+// follows jumps, calls and both sides of skips from the entry point; BNNN and anything
+// past the end of the rom stop the walk
+pub fn reachable(rom: &[u8], load_address: u16) -> Vec<u16> {
+    let end = load_address as usize + rom.len();
+    let mut seen = HashSet::new();
+    let mut pending = vec![load_address];
+    let mut found = vec![];
+
+    while let Some(address) = pending.pop() {
+        if address as usize >= end || address < load_address || !seen.insert(address) {
+            continue;
+        }
+        let i = (address - load_address) as usize;
+        let op = (rom[i] as u16) << 8 | *rom.get(i + 1).unwrap_or(&0) as u16;
+        found.push(address);
+
+        let next = address.wrapping_add(2);
+        match op & 0xF000 {
+            _ if op == 0x00EE || op == 0x00FD => {}
+            0x1000 => pending.push(op & 0x0FFF),
+            0x2000 => {
+                pending.push(op & 0x0FFF);
+                pending.push(next);
+            }
+            0xB000 => {}
+            0x3000 | 0x4000 | 0x9000 => skip(rom, load_address, next, &mut pending),
+            0x5000 if op & 0x000F == 0 => skip(rom, load_address, next, &mut pending),
+            0xE000 if matches!(op & 0x00FF, 0x9E | 0xA1) => {
+                skip(rom, load_address, next, &mut pending)
+            }
+            _ if op == 0xF000 => pending.push(next.wrapping_add(2)),
+            _ => pending.push(next),
+        }
+    }
+
+    found.sort_unstable();
+    found
+}
+
+// both the next instruction and the one after it, stepping over F000 NNNN
+fn skip(rom: &[u8], load_address: u16, next: u16, pending: &mut Vec<u16>) {
+    pending.push(next);
+    let i = next.wrapping_sub(load_address) as usize;
+    let long = rom.get(i) == Some(&0xF0) && rom.get(i + 1) == Some(&0x00);
+    pending.push(next.wrapping_add(if long { 4 } else { 2 }));
+}
+
+// looks at the straight-line code after an FX55/FX65 for the next instruction touching I
+fn uses_i_next(rom: &[u8], load_address: u16, address: u16) -> bool {
+    let mut i = (address - load_address) as usize + 2;
+    while i + 1 < rom.len() {
+        let op = (rom[i] as u16) << 8 | rom[i + 1] as u16;
+        match op & 0xF000 {
+            0xA000 => return false,
+            0xD000 => return true,
+            0xF000 => match op & 0x00FF {
+                0x29 | 0x30 => return false,
+                0x00 if op == 0xF000 => return false,
+                0x33 | 0x55 | 0x65 | 0x1E => return true,
+                _ => {}
+            },
+            // control flow: whatever comes next isn't known
+            0x0000 | 0x1000 | 0x2000 | 0x3000 | 0x4000 | 0x5000 | 0x9000 | 0xB000 | 0xE000 => {
+                return false
+            }
+            _ => {}
+        }
+        i += 2;
+    }
+    false
+}
+
+// ETI-660 programs start at 0x600, so their jumps and calls all point past it; read as
+// loaded at 0x200 they would land beyond the end of the rom
+fn looks_like_eti660(rom: &[u8]) -> bool {
+    let end = rom.len();
+    let targets: Vec<u16> = rom
+        .chunks_exact(2)
+        .map(|pair| (pair[0] as u16) << 8 | pair[1] as u16)
+        .filter(|op| matches!(op & 0xF000, 0x1000 | 0x2000))
+        .map(|op| op & 0x0FFF)
+        .collect();
+
+    // the first instruction is almost always a jump over the data, and read as loaded at
+    // 0x200 it would land past the end of the rom
+    let first = match rom {
+        [high, low, ..] if high & 0xF0 == 0x10 => (*high as u16 & 0x0F) << 8 | *low as u16,
+        _ => return false,
+    };
+    first as usize >= 0x200 + end
+        && targets
+            .iter()
+            .all(|&target| target >= 0x600 && (target as usize) < 0x600 + end)
+}
@@ -0,0 +1,134 @@
+use super::framebuffer::Framebuffer;
+use super::palette::{blend, Palette};
+
+// turns the cpu framebuffer into an RGB image, optionally letting pixels that switch
+// off fade out over a few frames like a phosphor screen would, which hides the flicker
+// of games that erase and redraw their sprites every frame
+pub struct Display {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+
+    persistence: u8,
+    // last lit value of every pixel and the frames since it went dark
+    last: Vec<u8>,
+    age: Vec<u8>,
+    fading: bool,
+}
+
+impl Display {
+    pub fn new(width: usize, height: usize, persistence: u8) -> Self {
+        Self {
+            width,
+            height,
+            rgb: vec![0; width * height * 3],
+
+            persistence,
+            last: vec![0; width * height],
+            age: vec![persistence; width * height],
+            fading: false,
+        }
+    }
+
+    // starts over at a new emulated resolution, dropping any fades in progress
+    pub fn resize(&mut self, width: usize, height: usize) {
+        *self = Self::new(width, height, self.persistence);
+    }
+
+    // renders one frame and returns the area of `rgb` that changed as (x, y, width,
+    // height), if any. only the rows the framebuffer marks dirty are rendered, except with
+    // persistence on, which moves every fade on by a frame
+    pub fn render(
+        &mut self,
+        gfx: &Framebuffer,
+        palette: &Palette,
+    ) -> Option<(usize, usize, usize, usize)> {
+        let background = palette.color(0);
+        let steps = self.persistence as u16 + 1;
+        let fading = self.fading;
+        self.fading = false;
+
+        let rows: Vec<usize> = if self.persistence == 0 {
+            gfx.dirty_rows().filter(|&y| y < self.height).collect()
+        } else {
+            (0..self.height.min(gfx.height())).collect()
+        };
+        let width = self.width.min(gfx.width());
+        for &y in &rows {
+            for x in 0..width {
+                let i = x + y * self.width;
+                let pixel = gfx.pixel(x, y);
+                let color = if pixel != 0 {
+                    self.last[i] = pixel;
+                    self.age[i] = 0;
+                    palette.color(pixel)
+                } else if self.age[i] < self.persistence {
+                    self.age[i] += 1;
+                    self.fading = true;
+                    blend(
+                        palette.color(self.last[i]),
+                        background,
+                        self.age[i] as u16,
+                        steps,
+                    )
+                } else {
+                    background
+                };
+                self.rgb[i * 3..i * 3 + 3].copy_from_slice(&color);
+            }
+        }
+
+        if self.persistence == 0 || !(fading || self.fading) {
+            gfx.dirty_rect()
+        } else {
+            Some((0, 0, self.width, self.height))
+        }
+    }
+
+    // whether pixels are still fading out and the next frame needs rendering anyway
+    pub fn fading(&self) -> bool {
+        self.fading
+    }
+}
+
+// nearest neighbour upscale of an RGB image by a whole factor
+pub fn upscale(rgb: &[u8], width: usize, height: usize, factor: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rgb.len() * factor * factor);
+    for y in 0..height {
+        let mut row = Vec::with_capacity(width * factor * 3);
+        for pixel in rgb[y * width * 3..(y + 1) * width * 3].chunks(3) {
+            for _ in 0..factor {
+                row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..factor {
+            out.extend_from_slice(&row);
+        }
+    }
+    out
+}
+
+// largest rectangle with the screen's aspect ratio that fits the window, centred, as
+// (x, y, width, height); integer scaling keeps every CHIP-8 pixel the same size
+pub fn letterbox(window: (u32, u32), screen: (u32, u32), integer: bool) -> (i32, i32, u32, u32) {
+    let (window_width, window_height) = window;
+    let (screen_width, screen_height) = screen;
+
+    let (width, height) = if integer {
+        let scale = (window_width / screen_width)
+            .min(window_height / screen_height)
+            .max(1);
+        (screen_width * scale, screen_height * scale)
+    } else if window_width * screen_height > window_height * screen_width {
+        (window_height * screen_width / screen_height, window_height)
+    } else {
+        (window_width, window_width * screen_height / screen_width)
+    };
+
+    (
+        (window_width as i32 - width as i32) / 2,
+        (window_height as i32 - height as i32) / 2,
+        width,
+        height,
+    )
+}
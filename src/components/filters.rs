@@ -0,0 +1,203 @@
+use super::display::upscale;
+
+// fewest host pixels per CHIP-8 pixel when any filter is on, enough room for the grid
+// and scanlines and a round of scale2x
+pub const MIN_FILTER_SCALE: usize = 2;
+
+// software post-processing of the rendered frame, applied on the CPU before upload
+#[derive(Default)]
+pub struct Filters {
+    pub scanlines: bool,
+    pub grid: bool,
+    pub bloom: bool,
+    pub smooth: bool,
+}
+
+impl Filters {
+    pub fn from_names(names: &[String]) -> Result<Self, String> {
+        let mut filters = Self::default();
+        for name in names {
+            filters.enable(name)?;
+        }
+        Ok(filters)
+    }
+
+    // turns a filter on, naming it twice leaves it on
+    pub fn enable(&mut self, name: &str) -> Result<(), String> {
+        *self.filter(name)? = true;
+        Ok(())
+    }
+
+    pub fn toggle(&mut self, name: &str) -> Result<bool, String> {
+        let filter = self.filter(name)?;
+        *filter = !*filter;
+        Ok(*filter)
+    }
+
+    fn filter(&mut self, name: &str) -> Result<&mut bool, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "scanlines" => Ok(&mut self.scanlines),
+            "grid" => Ok(&mut self.grid),
+            "bloom" => Ok(&mut self.bloom),
+            "smooth" | "scale2x" | "epx" => Ok(&mut self.smooth),
+            _ => Err(format!("Unknown filter: {}", name)),
+        }
+    }
+
+    // filters run at the integer scale the screen is shown at, rounded up to an even
+    // one for scale2x
+    pub fn scale(&self, shown: usize) -> usize {
+        if !(self.scanlines || self.grid || self.bloom || self.smooth) {
+            return 1;
+        }
+        let scale = shown.max(MIN_FILTER_SCALE);
+        if self.smooth {
+            scale + scale % 2
+        } else {
+            scale
+        }
+    }
+
+    // returns the frame at `scale` times the size, as picked by `scale()`
+    pub fn apply(&self, rgb: &[u8], width: usize, height: usize, scale: usize) -> Vec<u8> {
+        if scale == 1 {
+            return rgb.to_vec();
+        }
+
+        let mut out = if self.smooth {
+            // as many rounds of scale2x as fit, then plain pixel copies for the rest
+            let mut out = scale2x(rgb, width, height);
+            let mut factor = 2;
+            if scale.is_multiple_of(4) {
+                out = scale2x(&out, width * 2, height * 2);
+                factor = 4;
+            }
+            upscale(&out, width * factor, height * factor, scale / factor)
+        } else {
+            upscale(rgb, width, height, scale)
+        };
+        let (width, height) = (width * scale, height * scale);
+
+        if self.grid {
+            // dark gap along the right and bottom edge of every CHIP-8 pixel
+            for y in 0..height {
+                for x in 0..width {
+                    if x % scale == scale - 1 || y % scale == scale - 1 {
+                        darken(&mut out[(y * width + x) * 3..(y * width + x) * 3 + 3], 0.55);
+                    }
+                }
+            }
+        }
+
+        if self.scanlines {
+            for y in (1..height).step_by(2) {
+                for pixel in out[y * width * 3..(y + 1) * width * 3].chunks_mut(3) {
+                    darken(pixel, 0.7);
+                }
+            }
+        }
+
+        if self.bloom {
+            // lit pixels spill some of their light onto darker neighbours
+            let blurred = box_blur(&out, width, height, scale);
+            for (dst, glow) in out.iter_mut().zip(blurred) {
+                if glow > *dst {
+                    *dst += ((glow - *dst) as f32 * 0.6) as u8;
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn darken(pixel: &mut [u8], amount: f32) {
+    for channel in pixel.iter_mut() {
+        *channel = (*channel as f32 * amount) as u8;
+    }
+}
+
+// scale2x / EPX: doubles the image, rounding off diagonal edges
+fn scale2x(rgb: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let at = |x: usize, y: usize| {
+        let i = (y * width + x) * 3;
+        &rgb[i..i + 3]
+    };
+
+    let mut out = vec![0; rgb.len() * 4];
+    let out_width = width * 2;
+    for y in 0..height {
+        for x in 0..width {
+            let p = at(x, y);
+            let a = at(x, y.saturating_sub(1));
+            let b = at((x + 1).min(width - 1), y);
+            let c = at(x.saturating_sub(1), y);
+            let d = at(x, (y + 1).min(height - 1));
+
+            let e = [
+                if c == a && c != d && a != b { a } else { p },
+                if a == b && a != c && b != d { b } else { p },
+                if d == c && d != b && c != a { c } else { p },
+                if b == d && b != a && d != c { d } else { p },
+            ];
+            for (i, color) in e.iter().enumerate() {
+                let o = ((y * 2 + i / 2) * out_width + x * 2 + i % 2) * 3;
+                out[o..o + 3].copy_from_slice(color);
+            }
+        }
+    }
+    out
+}
+
+// separable box blur with the given radius, clamped at the edges
+fn box_blur(rgb: &[u8], width: usize, height: usize, radius: usize) -> Vec<u8> {
+    let pass = |src: &[u8], horizontal: bool| {
+        let mut dst = vec![0; src.len()];
+        let (outer, inner) = if horizontal {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let index = |o: usize, i: usize| {
+            if horizontal {
+                (o * width + i) * 3
+            } else {
+                (i * width + o) * 3
+            }
+        };
+
+        // a running sum over the window keeps the cost flat however large the radius
+        for o in 0..outer {
+            let mut sum = [0u32; 3];
+            for j in 0..radius.min(inner) {
+                let k = index(o, j);
+                for c in 0..3 {
+                    sum[c] += src[k + c] as u32;
+                }
+            }
+            for i in 0..inner {
+                if i + radius < inner {
+                    let k = index(o, i + radius);
+                    for c in 0..3 {
+                        sum[c] += src[k + c] as u32;
+                    }
+                }
+                if i > radius {
+                    let k = index(o, i - radius - 1);
+                    for c in 0..3 {
+                        sum[c] -= src[k + c] as u32;
+                    }
+                }
+                let from = i.saturating_sub(radius);
+                let to = (i + radius).min(inner - 1);
+                let k = index(o, i);
+                for c in 0..3 {
+                    dst[k + c] = (sum[c] / (to - from + 1) as u32) as u8;
+                }
+            }
+        }
+        dst
+    };
+
+    pass(&pass(rgb, true), false)
+}
@@ -0,0 +1,164 @@
+// the widest screen there is, SUPER-CHIP's 128 pixels, fits a row in one u128
+pub const MAX_WIDTH: usize = 128;
+
+// the screen, one bit per pixel: each of the two XO-CHIP planes keeps a u128 per row with
+// the leftmost pixel in the top bit, so a sprite row is drawn with a shift and an XOR and
+// collides where an AND with the row isn't zero
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    planes: [Vec<u128>; 2],
+    // the pixels of each row that changed since the frontend last marked the screen clean,
+    // laid out like the rows themselves
+    dirty: Vec<u128>,
+}
+
+// two screens are the same if they show the same pixels, whatever either has presented
+impl PartialEq for Framebuffer {
+    fn eq(&self, other: &Self) -> bool {
+        (self.width, self.height, &self.planes) == (other.width, other.height, &other.planes)
+    }
+}
+
+impl Eq for Framebuffer {}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width <= MAX_WIDTH, "screens are at most 128 pixels wide");
+        Self {
+            width,
+            height,
+            planes: [vec![0; height], vec![0; height]],
+            // a new screen has never been shown
+            dirty: vec![!0 << (MAX_WIDTH - width); height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // the bits of a row that are on screen
+    fn mask(&self) -> u128 {
+        !0 << (MAX_WIDTH - self.width)
+    }
+
+    // the pixel at (x, y): bit 0 set if it is lit in the first plane, bit 1 in the second
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let shift = MAX_WIDTH - 1 - x;
+        ((self.planes[0][y] >> shift) & 1 | ((self.planes[1][y] >> shift) & 1) << 1) as u8
+    }
+
+    // a row of one plane (0 or 1), pixel x in bit 127 - x
+    pub fn row(&self, plane: usize, y: usize) -> u128 {
+        self.planes[plane][y]
+    }
+
+    pub fn set_row(&mut self, plane: usize, y: usize, row: u128) {
+        let row = row & self.mask();
+        self.dirty[y] |= self.planes[plane][y] ^ row;
+        self.planes[plane][y] = row;
+    }
+
+    // XORs a row of up to 16 sprite pixels, the first in the top bit of `sprite`, into the
+    // selected planes at (x, y); whatever falls off the right edge is clipped, or with `wrap`
+    // comes back in on the left. true if a lit pixel was turned off
+    pub fn draw_row(&mut self, planes: u8, x: usize, y: usize, sprite: u16, wrap: bool) -> bool {
+        let sprite = (sprite as u128) << (MAX_WIDTH - 16);
+        let mut bits = sprite >> x;
+        if wrap {
+            // the pixels past the edge, moved back by the screen's width
+            bits |= sprite.checked_shl((self.width - x) as u32).unwrap_or(0);
+        }
+        let bits = bits & self.mask();
+        let mut collision = false;
+        for (plane, rows) in self.planes.iter_mut().enumerate() {
+            if planes & (1 << plane) != 0 {
+                collision |= rows[y] & bits != 0;
+                rows[y] ^= bits;
+                self.dirty[y] |= bits;
+            }
+        }
+        collision
+    }
+
+    // turns off every pixel of the selected planes
+    pub fn clear(&mut self, planes: u8) {
+        for (plane, rows) in self.planes.iter_mut().enumerate() {
+            if planes & (1 << plane) != 0 {
+                for (row, dirty) in rows.iter_mut().zip(self.dirty.iter_mut()) {
+                    *dirty |= std::mem::take(row);
+                }
+            }
+        }
+    }
+
+    // moves the selected planes by (dx, dy) pixels, filling in with blank pixels
+    pub fn scroll(&mut self, planes: u8, dx: isize, dy: isize) {
+        let (mask, height) = (self.mask(), self.height as isize);
+        for (plane, rows) in self.planes.iter_mut().enumerate() {
+            if planes & (1 << plane) == 0 {
+                continue;
+            }
+            let old = rows.clone();
+            for (y, row) in rows.iter_mut().enumerate() {
+                let from = y as isize - dy;
+                let previous = *row;
+                *row = if (0..height).contains(&from) {
+                    let moved = old[from as usize];
+                    match dx {
+                        0 => moved,
+                        dx if dx > 0 => moved.checked_shr(dx as u32).unwrap_or(0),
+                        dx => moved.checked_shl(-dx as u32).unwrap_or(0),
+                    }
+                } else {
+                    0
+                } & mask;
+                self.dirty[y] |= previous ^ *row;
+            }
+        }
+    }
+
+    // the rows with pixels that changed since the screen was last marked clean
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.height).filter(move |&y| self.dirty[y] != 0)
+    }
+
+    // the smallest rectangle around every changed pixel as (x, y, width, height), for
+    // frontends that only upload or send what changed; None if nothing did
+    pub fn dirty_rect(&self) -> Option<(usize, usize, usize, usize)> {
+        let top = self.dirty.iter().position(|&row| row != 0)?;
+        let bottom = self.dirty.iter().rposition(|&row| row != 0)?;
+        let columns = self.dirty.iter().fold(0, |columns, &row| columns | row);
+        let left = columns.leading_zeros() as usize;
+        let right = MAX_WIDTH - columns.trailing_zeros() as usize;
+        Some((left, top, right - left, bottom + 1 - top))
+    }
+
+    // once the frontend has shown the screen
+    pub fn mark_clean(&mut self) {
+        self.dirty.iter_mut().for_each(|row| *row = 0);
+    }
+
+    // for when the whole screen has to be shown again, after a palette change say
+    pub fn mark_dirty(&mut self) {
+        let mask = self.mask();
+        self.dirty.iter_mut().for_each(|row| *row = mask);
+    }
+
+    // every pixel as `pixel` gives it, row by row from the top left, for renderers
+    pub fn pixels(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..self.height).flat_map(move |y| {
+            let (first, second) = (self.planes[0][y], self.planes[1][y]);
+            (0..self.width).map(move |x| {
+                let shift = MAX_WIDTH - 1 - x;
+                ((first >> shift) & 1 | ((second >> shift) & 1) << 1) as u8
+            })
+        })
+    }
+}
@@ -1,35 +1,158 @@
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use std::option::Option;
+use super::config::KeyConfig;
+use super::controller::ControllerBindings;
+
+// host keys for the CHIP-8 keys 0-F, laid out as the 1234/QWER/ASDF/ZXCV block:
+//   1 2 3 C
+//   4 5 6 D
+//   7 8 9 E
+//   A 0 B F
+const QWERTY: [&str; 16] = [
+    "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V",
+];
+// the AZERTY number row needs shift, so match it by position instead
+const AZERTY: [&str; 16] = [
+    "X", "scan:1", "scan:2", "scan:3", "A", "Z", "E", "Q", "S", "D", "W", "C", "scan:4", "R", "F",
+    "V",
+];
+const QWERTZ: [&str; 16] = [
+    "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Y", "C", "4", "R", "F", "V",
+];
+const DVORAK: [&str; 16] = [
+    "Q", "1", "2", "3", "'", ",", ".", "A", "O", "E", ";", "J", "4", "P", "U", "K",
+];
+const COLEMAK: [&str; 16] = [
+    "X", "1", "2", "3", "Q", "W", "F", "A", "R", "S", "Z", "C", "4", "P", "T", "V",
+];
+
+pub const LAYOUTS: [(&str, [&str; 16]); 5] = [
+    ("qwerty", QWERTY),
+    ("azerty", AZERTY),
+    ("qwertz", QWERTZ),
+    ("dvorak", DVORAK),
+    ("colemak", COLEMAK),
+];
+
+// host keys are kept as SDL key names, the frontend resolves them to SDL keys
+pub struct KeyBindings {
+    keys: Vec<(String, u8)>,
+}
+
+impl KeyBindings {
+    pub fn from_config(config: &KeyConfig) -> Result<Self, String> {
+        let scancodes = config.scancodes.unwrap_or(false);
+
+        // scancodes are named after the US keyboard, so every layout shares the qwerty positions
+        let layout = if scancodes {
+            QWERTY
+        } else {
+            let name = config.layout.as_deref().unwrap_or("qwerty");
+            LAYOUTS
+                .iter()
+                .find(|(layout, _)| layout.eq_ignore_ascii_case(name))
+                .map(|(_, keys)| *keys)
+                .ok_or(format!("Unknown key layout: {}", name))?
+        };
+
+        let mut names: Vec<Vec<String>> =
+            layout.iter().map(|name| vec![name.to_string()]).collect();
+        for (key, hosts) in &config.bindings {
+            let i = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|i| *i < 16)
+                .ok_or(format!("Unknown CHIP-8 key: {}", key))?;
+            names[i as usize] = hosts.names();
+        }
+
+        let mut keys = vec![];
+        for (i, hosts) in names.iter().enumerate() {
+            for name in hosts {
+                // with scancodes on every name is matched by position
+                let name = if scancodes && !name.starts_with("scan:") {
+                    format!("scan:{}", name)
+                } else {
+                    name.clone()
+                };
+                keys.push((name, i as u8));
+            }
+        }
+
+        Ok(Self { keys })
+    }
+
+    // host key names and the CHIP-8 keys they press, "scan:" names are scancodes
+    pub fn keys(&self) -> &[(String, u8)] {
+        &self.keys
+    }
+
+    // host key names bound to a CHIP-8 key, for telling the user what to press
+    pub fn names_for(&self, key: u8) -> Vec<String> {
+        self.keys
+            .iter()
+            .filter(|(_, k)| *k == key)
+            .map(|(name, _)| name.strip_prefix("scan:").unwrap_or(name).to_string())
+            .collect()
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self::from_config(&KeyConfig::default()).unwrap()
+    }
+}
 
 pub struct Keypad {
     pub key: [u8; 16],
+    bindings: KeyBindings,
+    controller: ControllerBindings,
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Keypad {
     pub fn new() -> Self {
-        Self { key: [0; 16] }
-    }
-
-    pub fn press(&mut self, key: Option<Keycode>, state: u8) {
-        match key {
-            Some(Keycode::Num1) => self.key[1] = state,
-            Some(Keycode::Num2) => self.key[2] = state,
-            Some(Keycode::Num3) => self.key[3] = state,
-            Some(Keycode::Num4) => self.key[0xC] = state,
-            Some(Keycode::Q) => self.key[4] = state,
-            Some(Keycode::W) => self.key[5] = state,
-            Some(Keycode::E) => self.key[6] = state,
-            Some(Keycode::R) => self.key[0xD] = state,
-            Some(Keycode::A) => self.key[7] = state,
-            Some(Keycode::S) => self.key[8] = state,
-            Some(Keycode::D) => self.key[9] = state,
-            Some(Keycode::F) => self.key[0xE] = state,
-            Some(Keycode::Z) => self.key[0xA] = state,
-            Some(Keycode::X) => self.key[0] = state,
-            Some(Keycode::C) => self.key[0xB] = state,
-            Some(Keycode::V) => self.key[0xF] = state,
-            _ => {}
+        Self {
+            key: [0; 16],
+            bindings: KeyBindings::default(),
+            controller: ControllerBindings::default(),
+        }
+    }
+
+    pub fn set_bindings(&mut self, bindings: KeyBindings) {
+        self.bindings = bindings;
+        self.key = [0; 16];
+    }
+
+    pub fn set_controller_bindings(&mut self, controller: ControllerBindings) {
+        self.controller = controller;
+        self.key = [0; 16];
+    }
+
+    pub fn bindings(&self) -> &KeyBindings {
+        &self.bindings
+    }
+
+    pub fn controller(&self) -> &ControllerBindings {
+        &self.controller
+    }
+
+    pub fn press(&mut self, key: u8, state: u8) {
+        self.key[key as usize] = state;
+    }
+
+    // controls are named as SDL names them, e.g. "dpup" or "leftx"
+    pub fn press_button(&mut self, button: &str, state: u8) {
+        for key in self.controller.button(button) {
+            self.key[key as usize] = state;
+        }
+    }
+
+    pub fn move_axis(&mut self, axis: &str, value: i16) {
+        for (key, state) in self.controller.axis(axis, value) {
+            self.key[key as usize] = state;
         }
     }
 }
@@ -0,0 +1,274 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Cursor, Read, Write};
+use std::path::Path;
+
+use super::cartridge::{Cartridge, CartridgeOptions};
+
+// file extensions taken to be programs when picking one out of a zip archive
+const ROM_EXTENSIONS: [&str; 9] = ["ch8", "c8", "sc8", "xo8", "c8x", "rom", "bin", "hex", "gif"];
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    // the program doesn't fit between the load address and the end of memory
+    TooBig { size: usize, capacity: usize },
+    // a hex dump, Intel HEX file, archive or cartridge that couldn't be read
+    Format(String),
+    Empty,
+    // the program would overwrite the fonts at this address
+    OverFont(u16),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::TooBig { size, capacity } => write!(
+                f,
+                "rom is {} bytes, but only {} fit in memory",
+                size, capacity
+            ),
+            LoadError::Format(e) => write!(f, "{}", e),
+            LoadError::Empty => write!(f, "rom is empty"),
+            LoadError::OverFont(address) => {
+                write!(f, "rom would overwrite the font at 0x{:X}", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+// a program ready to be copied into memory
+pub struct Rom {
+    pub data: Vec<u8>,
+    // where the file says it goes (Intel HEX), instead of the usual load address
+    pub address: Option<u16>,
+    pub cartridge: Option<CartridgeOptions>,
+}
+
+// reads a rom from a file, or from stdin for "-"
+pub fn read_file(filename: &str) -> Result<Rom, LoadError> {
+    if filename == "-" {
+        // stdin is taken, so an archive can't ask which rom to run
+        return decode(read_all(io::stdin().lock())?, false);
+    }
+
+    let file = File::open(filename)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", filename, e)))?;
+    decode(read_all(file)?, true)
+}
+
+pub fn read_all<R: Read>(mut reader: R) -> Result<Vec<u8>, LoadError> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// works out what kind of file the data is: an Octo cartridge, a zip archive, Intel HEX,
+// a hex text dump or, failing all of those, a plain binary
+pub fn decode(data: Vec<u8>, interactive: bool) -> Result<Rom, LoadError> {
+    let rom = |data: Vec<u8>| Rom {
+        data,
+        address: None,
+        cartridge: None,
+    };
+
+    let rom = if Cartridge::is_cartridge(&data) {
+        let cartridge = Cartridge::decode(&data)
+            .map_err(|e| LoadError::Format(format!("couldn't read Octo cartridge: {}", e)))?;
+        Rom {
+            data: cartridge.program,
+            address: None,
+            cartridge: Some(cartridge.options),
+        }
+    } else if data.starts_with(b"PK\x03\x04") {
+        return decode(unzip(data, interactive)?, interactive);
+    } else if let Some(text) = as_text(&data) {
+        if text.trim_start().starts_with(':') {
+            let (data, address) = intel_hex(text)?;
+            Rom {
+                data,
+                address: Some(address),
+                cartridge: None,
+            }
+        } else {
+            match hex_text(text) {
+                Some(data) => rom(data),
+                None => rom(data),
+            }
+        }
+    } else {
+        rom(data)
+    };
+
+    if rom.data.is_empty() {
+        return Err(LoadError::Empty);
+    }
+    Ok(rom)
+}
+
+fn as_text(data: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(data).ok()?;
+    if text
+        .chars()
+        .all(|c| c.is_ascii_graphic() || c.is_ascii_whitespace())
+    {
+        Some(text)
+    } else {
+        None
+    }
+}
+
+// the hex dumps Octo and most assemblers export: "0x00 0xE0 0xA2 0x2A", "00E0 A22A" or the
+// like, with commas and # or ; comments allowed
+fn hex_text(text: &str) -> Option<Vec<u8>> {
+    let mut data = vec![];
+    for line in text.lines() {
+        let line = line.split(['#', ';']).next().unwrap_or("");
+        for word in line.split(|c: char| c.is_whitespace() || c == ',') {
+            let digits = word
+                .strip_prefix("0x")
+                .or_else(|| word.strip_prefix("0X"))
+                .unwrap_or(word);
+            if digits.is_empty() {
+                continue;
+            }
+            if digits.len() % 2 != 0 {
+                return None;
+            }
+            for i in (0..digits.len()).step_by(2) {
+                data.push(u8::from_str_radix(digits.get(i..i + 2)?, 16).ok()?);
+            }
+        }
+    }
+    Some(data)
+}
+
+// Intel HEX: ":LLAAAATT<data>CC" records holding bytes at absolute addresses; returns the
+// bytes from the lowest address up and that address
+fn intel_hex(text: &str) -> Result<(Vec<u8>, u16), LoadError> {
+    let error = |line: usize, message: &str| {
+        LoadError::Format(format!("Intel HEX line {}: {}", line + 1, message))
+    };
+
+    let mut chunks: Vec<(usize, Vec<u8>)> = vec![];
+    let mut base = 0;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let record = line
+            .strip_prefix(':')
+            .and_then(hex_text)
+            .filter(|record| record.len() >= 5)
+            .ok_or_else(|| error(n, "not a record"))?;
+
+        let length = record[0] as usize;
+        if record.len() != length + 5 {
+            return Err(error(n, "wrong length"));
+        }
+        if record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(error(n, "bad checksum"));
+        }
+
+        let address = (record[1] as usize) << 8 | record[2] as usize;
+        let data = &record[4..4 + length];
+        match record[3] {
+            0x00 => chunks.push((base + address, data.to_vec())),
+            0x01 => break,
+            // extended segment and linear addresses
+            0x02 if length == 2 => base = ((data[0] as usize) << 8 | data[1] as usize) << 4,
+            0x04 if length == 2 => base = ((data[0] as usize) << 8 | data[1] as usize) << 16,
+            0x03 | 0x05 => {}
+            _ => return Err(error(n, "unknown record type")),
+        }
+    }
+
+    let start = chunks
+        .iter()
+        .map(|(address, _)| *address)
+        .min()
+        .unwrap_or(0);
+    let end = chunks
+        .iter()
+        .map(|(address, data)| address + data.len())
+        .max()
+        .unwrap_or(0);
+    if end > 0x10000 {
+        return Err(LoadError::Format(
+            "Intel HEX data lies beyond 64 KB".to_string(),
+        ));
+    }
+
+    let mut image = vec![0; end - start];
+    for (address, data) in chunks {
+        image[address - start..address - start + data.len()].copy_from_slice(&data);
+    }
+    Ok((image, start as u16))
+}
+
+// takes the one rom out of an archive, asking which when there are several
+fn unzip(data: Vec<u8>, interactive: bool) -> Result<Vec<u8>, LoadError> {
+    let error = |e: zip::result::ZipError| LoadError::Format(format!("bad zip archive: {}", e));
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(error)?;
+
+    let mut names: Vec<String> = archive
+        .file_names()
+        .filter(|name| {
+            let extension = Path::new(name)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .unwrap_or("")
+                .to_ascii_lowercase();
+            !name.ends_with('/') && ROM_EXTENSIONS.contains(&extension.as_str())
+        })
+        .map(|name| name.to_string())
+        .collect();
+    names.sort();
+
+    let name = match names.len() {
+        0 => return Err(LoadError::Format("no rom in the zip archive".to_string())),
+        1 => names.remove(0),
+        _ if !interactive => {
+            return Err(LoadError::Format(format!(
+                "the zip archive holds several roms: {}",
+                names.join(", ")
+            )))
+        }
+        _ => choose(names)?,
+    };
+
+    let entry = archive.by_name(&name).map_err(error)?;
+    let data = read_all(entry)?;
+    Ok(data)
+}
+
+fn choose(mut names: Vec<String>) -> Result<String, LoadError> {
+    eprintln!("The archive holds several roms:");
+    for (i, name) in names.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, name);
+    }
+
+    let stdin = io::stdin();
+    loop {
+        eprint!("Which one? ");
+        io::stderr().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Err(LoadError::Format("no rom chosen".to_string()));
+        }
+        match line.trim().parse::<usize>() {
+            Ok(i) if i >= 1 && i <= names.len() => return Ok(names.remove(i - 1)),
+            _ => eprintln!("Enter a number from 1 to {}", names.len()),
+        }
+    }
+}
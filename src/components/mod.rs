@@ -1,4 +1,25 @@
 pub mod audio;
+pub mod cartridge;
+pub mod cdp1802;
+pub mod config;
 pub mod constants;
+pub mod controller;
 pub mod cpu;
+pub mod database;
+pub mod detect;
+pub mod display;
+pub mod filters;
+pub mod font;
+pub mod framebuffer;
 pub mod keypad;
+pub mod loader;
+pub mod octo;
+pub mod options;
+pub mod palette;
+pub mod platform;
+pub mod recompiler;
+pub mod recorder;
+pub mod screenshot;
+pub mod timing;
+pub mod transpile;
+pub mod vip;
@@ -0,0 +1,834 @@
+use std::collections::{HashMap, VecDeque};
+
+// a small assembler for Octo (https://github.com/JohnEarnest/Octo) source, enough for the
+// programs Octo shares as cartridges; the rom it produces is meant to be loaded at 0x200
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler::new(tokenize(source)?);
+    assembler.run()?;
+    Ok(assembler.rom)
+}
+
+const START: usize = 0x200;
+
+// string literals keep their opening quote so they can't be mistaken for names
+fn tokenize(source: &str) -> Result<VecDeque<String>, String> {
+    let mut tokens = VecDeque::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            while chars.next().is_some_and(|c| c != '\n') {}
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::from("\"");
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => text.push('\n'),
+                        Some('t') => text.push('\t'),
+                        Some('0') => text.push('\0'),
+                        Some(c) => text.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    },
+                    Some(c) => text.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push_back(text);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push_back(word);
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn number(token: &str) -> Option<f64> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+// where a label's address goes once it is known
+#[derive(Clone, Copy)]
+enum Fixup {
+    // low 12 bits of the instruction
+    Address,
+    // both bytes, for `i := long` and `:pointer`
+    Long,
+    // the two `vN := NN` instructions of :unpack
+    Unpack(u8),
+    UnpackLong,
+}
+
+enum Flow {
+    // position of the jump over the block, patched at else or end
+    If(usize),
+    Else(usize),
+    // where the loop starts, and the jumps out of it for each while
+    Loop(usize, Vec<usize>),
+}
+
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+struct Assembler {
+    tokens: VecDeque<String>,
+    rom: Vec<u8>,
+    here: usize,
+
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, (Vec<String>, Vec<String>)>,
+    // name -> character -> (index in the alphabet, body)
+    stringmodes: HashMap<String, HashMap<char, (usize, Vec<String>)>>,
+
+    fixups: Vec<(String, usize, Fixup)>,
+    flow: Vec<Flow>,
+    next: Option<String>,
+}
+
+impl Assembler {
+    fn new(tokens: VecDeque<String>) -> Self {
+        let mut aliases = HashMap::new();
+        aliases.insert("unpack-hi".to_string(), 0x0);
+        aliases.insert("unpack-lo".to_string(), 0x1);
+        aliases.insert("compare-temp".to_string(), 0xF);
+
+        Self {
+            tokens,
+            rom: vec![],
+            here: START,
+
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases,
+            macros: HashMap::new(),
+            stringmodes: HashMap::new(),
+
+            fixups: vec![],
+            flow: vec![],
+            next: None,
+        }
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        // programs start with a jump to main
+        self.inst(0x10, 0x00);
+
+        while let Some(token) = self.tokens.pop_front() {
+            self.statement(&token)
+                .map_err(|e| format!("{} (near \"{}\")", e, token))?;
+        }
+
+        if !self.flow.is_empty() {
+            return Err("missing end or again".to_string());
+        }
+        let main = *self.labels.get("main").ok_or("no main label")?;
+        self.rom[0] = 0x10 | (main >> 8) as u8 & 0xF;
+        self.rom[1] = main as u8;
+
+        for (name, position, fixup) in std::mem::take(&mut self.fixups) {
+            let address = *self
+                .labels
+                .get(&name)
+                .ok_or(format!("undefined name: {}", name))?;
+            let i = position - START;
+            match fixup {
+                Fixup::Address => {
+                    self.rom[i] = (self.rom[i] & 0xF0) | (address >> 8) as u8 & 0xF;
+                    self.rom[i + 1] = address as u8;
+                }
+                Fixup::Long => {
+                    self.rom[i] = (address >> 8) as u8;
+                    self.rom[i + 1] = address as u8;
+                }
+                Fixup::Unpack(nibble) => {
+                    self.rom[i + 1] = nibble << 4 | (address >> 8) as u8 & 0xF;
+                    self.rom[i + 3] = address as u8;
+                }
+                Fixup::UnpackLong => {
+                    self.rom[i + 1] = (address >> 8) as u8;
+                    self.rom[i + 3] = address as u8;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        self.tokens
+            .pop_front()
+            .ok_or_else(|| "unexpected end of program".to_string())
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(format!("expected {}, found {}", expected, token))
+        }
+    }
+
+    fn emit(&mut self, byte: u8) {
+        let i = self.here - START;
+        if i >= self.rom.len() {
+            self.rom.resize(i + 1, 0);
+        }
+        self.rom[i] = byte;
+        self.here += 1;
+    }
+
+    fn inst(&mut self, a: u8, b: u8) {
+        if let Some(name) = self.next.take() {
+            self.labels.insert(name, self.here + 1);
+        }
+        self.emit(a);
+        self.emit(b);
+    }
+
+    fn define(&mut self, name: String, address: usize) -> Result<(), String> {
+        if self.labels.contains_key(&name) {
+            return Err(format!("label defined twice: {}", name));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn register_index(&self, token: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register);
+        }
+        let hex = token
+            .strip_prefix('v')
+            .or_else(|| token.strip_prefix('V'))?;
+        u8::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 1)
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        self.register_index(&token)
+            .ok_or(format!("expected a register, found {}", token))
+    }
+
+    // a number, constant, known label or { calculation }
+    fn constant(&mut self, token: &str) -> Result<Option<f64>, String> {
+        if token == "{" {
+            return self.calc().map(Some);
+        }
+        Ok(number(token)
+            .or_else(|| self.constants.get(token).copied())
+            .or_else(|| self.labels.get(token).map(|address| *address as f64)))
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        let value = self
+            .constant(&token)?
+            .ok_or(format!("expected a number, found {}", token))? as i64;
+        if !(-128..=255).contains(&value) {
+            return Err(format!("value doesn't fit in a byte: {}", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u8, String> {
+        let value = self.byte()?;
+        if value > 15 {
+            return Err(format!("value doesn't fit in a nibble: {}", value));
+        }
+        Ok(value)
+    }
+
+    // an address; labels that aren't defined yet are filled in at the end
+    fn address(&mut self, position: usize, fixup: Fixup) -> Result<usize, String> {
+        let token = self.next()?;
+        match self.constant(&token)? {
+            Some(value) => Ok(value as usize),
+            None if token.starts_with('"') => Err(format!("expected an address, found {}", token)),
+            None => {
+                self.fixups.push((token, position, fixup));
+                Ok(0)
+            }
+        }
+    }
+
+    fn address_inst(&mut self, op: u8) -> Result<(), String> {
+        let address = self.address(self.here, Fixup::Address)?;
+        if address > 0xFFF {
+            return Err(format!("address out of range: 0x{:X}", address));
+        }
+        self.inst(op << 4 | (address >> 8) as u8, address as u8);
+        Ok(())
+    }
+
+    fn placeholder(&mut self) -> usize {
+        let position = self.here;
+        self.inst(0x10, 0x00);
+        position
+    }
+
+    fn patch(&mut self, position: usize, target: usize) {
+        let i = position - START;
+        self.rom[i] = 0x10 | (target >> 8) as u8 & 0xF;
+        self.rom[i + 1] = target as u8;
+    }
+
+    fn statement(&mut self, token: &str) -> Result<(), String> {
+        match token {
+            ":" => {
+                let name = self.next()?;
+                self.define(name, self.here)?;
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = if self.peek() == Some("{") {
+                    self.next()?;
+                    self.calc()? as u8
+                } else {
+                    self.register()?
+                };
+                self.aliases.insert(name, register);
+            }
+            ":const" | ":calc" => {
+                let name = self.next()?;
+                let token = self.next()?;
+                let value = self
+                    .constant(&token)?
+                    .ok_or(format!("expected a value, found {}", token))?;
+                self.constants.insert(name, value);
+            }
+            ":byte" => {
+                let value = self.byte()?;
+                self.emit(value);
+            }
+            ":pointer" => {
+                let address = self.address(self.here, Fixup::Long)?;
+                self.emit((address >> 8) as u8);
+                self.emit(address as u8);
+            }
+            ":org" => {
+                let token = self.next()?;
+                let address = self
+                    .constant(&token)?
+                    .ok_or(format!("expected an address, found {}", token))?
+                    as usize;
+                if address < START {
+                    return Err(format!("can't :org below 0x200: 0x{:X}", address));
+                }
+                self.here = address;
+            }
+            ":next" => self.next = Some(self.next()?),
+            ":unpack" => {
+                let (fixup, nibble) = if self.peek() == Some("long") {
+                    self.next()?;
+                    (Fixup::UnpackLong, None)
+                } else {
+                    let nibble = self.nibble()?;
+                    (Fixup::Unpack(nibble), Some(nibble))
+                };
+                let address = self.address(self.here, fixup)?;
+                let high = match nibble {
+                    Some(nibble) => nibble << 4 | (address >> 8) as u8 & 0xF,
+                    None => (address >> 8) as u8,
+                };
+                let hi = self.aliases["unpack-hi"];
+                let lo = self.aliases["unpack-lo"];
+                self.inst(0x60 | hi, high);
+                self.inst(0x60 | lo, address as u8);
+            }
+            ":call" => self.address_inst(0x2)?,
+            ":macro" => {
+                let name = self.next()?;
+                let mut params = vec![];
+                loop {
+                    let token = self.next()?;
+                    if token == "{" {
+                        break;
+                    }
+                    params.push(token);
+                }
+                let body = self.block()?;
+                self.macros.insert(name, (params, body));
+            }
+            ":stringmode" => {
+                let name = self.next()?;
+                let alphabet = self.next()?;
+                let alphabet = alphabet
+                    .strip_prefix('"')
+                    .ok_or(format!("expected a string, found {}", alphabet))?
+                    .to_string();
+                self.expect("{")?;
+                let body = self.block()?;
+                let mode = self.stringmodes.entry(name).or_default();
+                for (i, c) in alphabet.chars().enumerate() {
+                    mode.insert(c, (i, body.clone()));
+                }
+            }
+            ":assert" => {
+                let mut message = "assertion failed".to_string();
+                if let Some(text) = self.peek().and_then(|token| token.strip_prefix('"')) {
+                    message = text.to_string();
+                    self.next()?;
+                }
+                self.expect("{")?;
+                if self.calc()? == 0.0 {
+                    return Err(message);
+                }
+            }
+            // debugger hints, which don't change the program
+            ":breakpoint" | ":proto" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                let token = self.next()?;
+                if token == "{" {
+                    self.calc()?;
+                }
+            }
+
+            ";" | "return" => self.inst(0x00, 0xEE),
+            "clear" => self.inst(0x00, 0xE0),
+            "hires" => self.inst(0x00, 0xFF),
+            "lores" => self.inst(0x00, 0xFE),
+            "exit" => self.inst(0x00, 0xFD),
+            "scroll-left" => self.inst(0x00, 0xFC),
+            "scroll-right" => self.inst(0x00, 0xFB),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.inst(0x00, 0xC0 | n);
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.inst(0x00, 0xD0 | n);
+            }
+            "audio" => self.inst(0xF0, 0x02),
+            "plane" => {
+                let n = self.nibble()?;
+                self.inst(0xF0 | n, 0x01);
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.inst(0xF0 | x, 0x33);
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    let op = if token == "save" { 0x2 } else { 0x3 };
+                    self.inst(0x50 | x, y << 4 | op);
+                } else {
+                    self.inst(0xF0 | x, if token == "save" { 0x55 } else { 0x65 });
+                }
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.inst(0xF0 | x, 0x75);
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.inst(0xF0 | x, 0x85);
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.inst(0xD0 | x, y << 4 | n);
+            }
+            "jump" => self.address_inst(0x1)?,
+            "jump0" => self.address_inst(0xB)?,
+            "native" => self.address_inst(0x0)?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let op = match token {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.inst(0xF0 | x, op);
+            }
+            "i" => self.i_statement()?,
+
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => self.branch(condition, false),
+                    "begin" => {
+                        self.branch(condition, true);
+                        let jump = self.placeholder();
+                        self.flow.push(Flow::If(jump));
+                    }
+                    other => return Err(format!("expected then or begin, found {}", other)),
+                }
+            }
+            "else" => match self.flow.pop() {
+                Some(Flow::If(jump)) => {
+                    let end = self.placeholder();
+                    self.patch(jump, self.here);
+                    self.flow.push(Flow::Else(end));
+                }
+                _ => return Err("else without if ... begin".to_string()),
+            },
+            "end" => match self.flow.pop() {
+                Some(Flow::If(jump)) | Some(Flow::Else(jump)) => self.patch(jump, self.here),
+                _ => return Err("end without begin".to_string()),
+            },
+            "loop" => self.flow.push(Flow::Loop(self.here, vec![])),
+            "while" => {
+                let condition = self.condition()?;
+                self.branch(condition, true);
+                let jump = self.placeholder();
+                match self
+                    .flow
+                    .iter_mut()
+                    .rev()
+                    .find(|flow| matches!(flow, Flow::Loop(..)))
+                {
+                    Some(Flow::Loop(_, exits)) => exits.push(jump),
+                    _ => return Err("while outside of a loop".to_string()),
+                }
+            }
+            "again" => match self.flow.pop() {
+                Some(Flow::Loop(start, exits)) => {
+                    let jump = self.placeholder();
+                    self.patch(jump, start);
+                    for exit in exits {
+                        self.patch(exit, self.here);
+                    }
+                }
+                _ => return Err("again without loop".to_string()),
+            },
+
+            _ if self.register_index(token).is_some() => {
+                let x = self.register_index(token).unwrap_or(0);
+                self.register_statement(x)?;
+            }
+            _ if self.macros.contains_key(token) => {
+                let (params, body) = self.macros[token].clone();
+                let mut args = HashMap::new();
+                for param in params {
+                    args.insert(param, self.next()?);
+                }
+                for token in body.into_iter().rev() {
+                    let token = args.get(&token).cloned().unwrap_or(token);
+                    self.tokens.push_front(token);
+                }
+            }
+            _ if self.stringmodes.contains_key(token) => {
+                let text = self.next()?;
+                let text = text
+                    .strip_prefix('"')
+                    .ok_or(format!("expected a string, found {}", text))?;
+                let mut expanded = vec![];
+                for (index, c) in text.chars().enumerate() {
+                    let (value, body) = self.stringmodes[token]
+                        .get(&c)
+                        .ok_or(format!("character not in string mode {}: {:?}", token, c))?;
+                    for word in body {
+                        expanded.push(match word.as_str() {
+                            "VALUE" => value.to_string(),
+                            "CHAR" => (c as u32).to_string(),
+                            "INDEX" => index.to_string(),
+                            _ => word.clone(),
+                        });
+                    }
+                }
+                for token in expanded.into_iter().rev() {
+                    self.tokens.push_front(token);
+                }
+            }
+            _ if token.starts_with('"') => return Err("unexpected string".to_string()),
+            _ => match self.constant(token)? {
+                // bare numbers are data
+                Some(value) if number(token).is_some() || token == "{" => {
+                    let value = value as i64;
+                    if !(-128..=255).contains(&value) {
+                        return Err(format!("value doesn't fit in a byte: {}", value));
+                    }
+                    self.emit(value as u8);
+                }
+                // anything else is a subroutine call
+                _ => {
+                    self.tokens.push_front(token.to_string());
+                    self.address_inst(0x2)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // the tokens up to the matching }
+    fn block(&mut self) -> Result<Vec<String>, String> {
+        let mut body = vec![];
+        let mut depth = 1;
+        loop {
+            let token = self.next()?;
+            match token.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    fn i_statement(&mut self) -> Result<(), String> {
+        match self.next()?.as_str() {
+            ":=" => match self.peek() {
+                Some("long") => {
+                    self.next()?;
+                    self.inst(0xF0, 0x00);
+                    let address = self.address(self.here, Fixup::Long)?;
+                    self.emit((address >> 8) as u8);
+                    self.emit(address as u8);
+                }
+                Some("hex") | Some("bighex") => {
+                    let op = if self.next()? == "hex" { 0x29 } else { 0x30 };
+                    let x = self.register()?;
+                    self.inst(0xF0 | x, op);
+                }
+                _ => self.address_inst(0xA)?,
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.inst(0xF0 | x, 0x1E);
+            }
+            other => return Err(format!("unknown operator for i: {}", other)),
+        }
+        Ok(())
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.peek().and_then(|token| self.register_index(token)) {
+            Some(y) => {
+                self.next()?;
+                Ok(Operand::Register(y))
+            }
+            None => Ok(Operand::Byte(self.byte()?)),
+        }
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), String> {
+        let op = self.next()?;
+        match op.as_str() {
+            ":=" => match self.peek() {
+                Some("key") => {
+                    self.next()?;
+                    self.inst(0xF0 | x, 0x0A);
+                }
+                Some("delay") => {
+                    self.next()?;
+                    self.inst(0xF0 | x, 0x07);
+                }
+                Some("random") => {
+                    self.next()?;
+                    let mask = self.byte()?;
+                    self.inst(0xC0 | x, mask);
+                }
+                _ => match self.operand()? {
+                    Operand::Register(y) => self.inst(0x80 | x, y << 4),
+                    Operand::Byte(n) => self.inst(0x60 | x, n),
+                },
+            },
+            "+=" => match self.operand()? {
+                Operand::Register(y) => self.inst(0x80 | x, y << 4 | 0x4),
+                Operand::Byte(n) => self.inst(0x70 | x, n),
+            },
+            "-=" => match self.operand()? {
+                Operand::Register(y) => self.inst(0x80 | x, y << 4 | 0x5),
+                Operand::Byte(n) => self.inst(0x70 | x, n.wrapping_neg()),
+            },
+            "|=" | "&=" | "^=" | "=-" | ">>=" | "<<=" => {
+                let y = self.register()?;
+                let n = match op.as_str() {
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    "=-" => 0x7,
+                    ">>=" => 0x6,
+                    _ => 0xE,
+                };
+                self.inst(0x80 | x, y << 4 | n);
+            }
+            _ => return Err(format!("unknown operator: {}", op)),
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<(u8, String, Option<Operand>), String> {
+        let x = self.register()?;
+        let op = self.next()?;
+        let operand = match op.as_str() {
+            "key" | "-key" => None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => Some(self.operand()?),
+            _ => return Err(format!("unknown comparison: {}", op)),
+        };
+        Ok((x, op, operand))
+    }
+
+    // emits code that skips the next instruction when the condition is `skip_when`
+    fn branch(&mut self, condition: (u8, String, Option<Operand>), skip_when: bool) {
+        let (x, op, operand) = condition;
+        let (op, skip_when) = match op.as_str() {
+            "!=" => ("==", !skip_when),
+            "-key" => ("key", !skip_when),
+            "<=" => (">", !skip_when),
+            ">=" => ("<", !skip_when),
+            op => (op, skip_when),
+        };
+
+        match (op, operand) {
+            ("key", _) => self.inst(0xE0 | x, if skip_when { 0x9E } else { 0xA1 }),
+            ("==", Some(Operand::Byte(n))) => self.inst(if skip_when { 0x30 } else { 0x40 } | x, n),
+            ("==", Some(Operand::Register(y))) => {
+                self.inst(if skip_when { 0x50 } else { 0x90 } | x, y << 4)
+            }
+            // < and > go through compare-temp: it takes the right side, then the subtraction
+            // leaves vF at 0 exactly when the comparison holds
+            (op, Some(operand)) => {
+                let t = self.aliases["compare-temp"];
+                match operand {
+                    Operand::Register(y) => self.inst(0x80 | t, y << 4),
+                    Operand::Byte(n) => self.inst(0x60 | t, n),
+                }
+                let subtract = if op == "<" { 0x7 } else { 0x5 };
+                self.inst(0x80 | t, x << 4 | subtract);
+                self.inst(0x3F, if skip_when { 0x00 } else { 0x01 });
+            }
+            (_, None) => {}
+        }
+    }
+
+    fn calc(&mut self) -> Result<f64, String> {
+        let value = self.calc_expr()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    // Octo evaluates right to left with every binary operator at the same precedence
+    fn calc_expr(&mut self) -> Result<f64, String> {
+        let value = self.calc_term()?;
+        match self.peek() {
+            Some(")") | Some("}") | None => Ok(value),
+            Some(_) => {
+                let op = self.next()?;
+                let rest = self.calc_expr()?;
+                let (a, b) = (value as i64, rest as i64);
+                let truth = |t: bool| if t { 1.0 } else { 0.0 };
+                Ok(match op.as_str() {
+                    "+" => value + rest,
+                    "-" => value - rest,
+                    "*" => value * rest,
+                    "/" => value / rest,
+                    "%" => value % rest,
+                    "pow" => value.powf(rest),
+                    "min" => value.min(rest),
+                    "max" => value.max(rest),
+                    "&" => (a & b) as f64,
+                    "|" => (a | b) as f64,
+                    "^" => (a ^ b) as f64,
+                    "<<" => a
+                        .checked_shl(b as u32)
+                        .ok_or(format!("shift out of range in calculation: {}", b))?
+                        as f64,
+                    ">>" => a
+                        .checked_shr(b as u32)
+                        .ok_or(format!("shift out of range in calculation: {}", b))?
+                        as f64,
+                    "<" => truth(value < rest),
+                    ">" => truth(value > rest),
+                    "<=" => truth(value <= rest),
+                    ">=" => truth(value >= rest),
+                    "==" => truth(value == rest),
+                    "!=" => truth(value != rest),
+                    _ => return Err(format!("unknown operator in calculation: {}", op)),
+                })
+            }
+        }
+    }
+
+    fn calc_term(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        let unary: Option<fn(f64) -> f64> = match token.as_str() {
+            "(" => {
+                let value = self.calc_expr()?;
+                self.expect(")")?;
+                return Ok(value);
+            }
+            "-" => Some(|v| -v),
+            "~" => Some(|v| !(v as i64) as f64),
+            "!" => Some(|v| if v == 0.0 { 1.0 } else { 0.0 }),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(unary) = unary {
+            return Ok(unary(self.calc_term()?));
+        }
+        if token == "@" {
+            let address = self.calc_term()? as usize;
+            return Ok(*address
+                .checked_sub(START)
+                .and_then(|i| self.rom.get(i))
+                .unwrap_or(&0) as f64);
+        }
+
+        match token.as_str() {
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => number(&token)
+                .or_else(|| self.constants.get(&token).copied())
+                .or_else(|| self.labels.get(&token).map(|address| *address as f64))
+                .or_else(|| self.register_index(&token).map(|register| register as f64))
+                .ok_or(format!("undefined name in calculation: {}", token)),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::str::FromStr;
+
+use super::font::Font;
+use super::recompiler::Engine;
+use super::timing::Timing;
+use super::vip::Cdp1802Mode;
+
+pub const USAGE: &str = "syntax: chip_8_rust [options] [rom_file]
+       chip_8_rust transpile [--load-address addr] rom_file [output.rs]
+rom_file can be a binary, hex text, Intel HEX, .zip or Octo cartridge .gif, or - for stdin
+transpile writes the rom's code as a Rust module, to stdout without an output file
+options:
+  --config file          settings file (default: chip8.toml)
+  --layout name          keyboard layout: qwerty, azerty, qwertz, dvorak or colemak
+  --platform id          run as a database platform: originalChip8, hybridVIP, eti660,
+                         modernChip8, chip48, superchip1, superchip or xochip
+  --ipf n                instructions per 60 Hz frame
+  --timing mode          instructions (a fixed number per frame) or vip (cycle costs
+                         of the COSMAC VIP, ignoring --ipf)
+  --engine name          interpreter, blocks (pre-decoded code run from a cache) or
+                         lockstep (blocks checked against the interpreter)
+  --cdp1802 mode         emulate the VIP's processor: routines (0NNN machine code) or
+                         interpreter (run --vip-interpreter instead of this one)
+  --vip-monitor file     VIP monitor ROM image, mapped at 0x8000
+  --vip-interpreter file CHIP-8 interpreter image for the VIP, loaded at 0x000
+  --load-address addr    where the rom is loaded and starts, e.g. 0x600
+  --font name            hex digit font: vip, chip48, dream6800, eti660 or octo
+  --font-address addr    where the font sits in memory, e.g. 0x050 (default: 0x000)
+  --database dir         directory with a chip-8 database to use over the bundled one
+  --palette name         color theme or palette file
+  --persistence frames   let pixels fade out over a number of frames
+  --filter names         post-processing, comma separated: scanlines, grid, bloom, smooth
+  --scale n              initial window scale (default: 10)
+  --integer-scale        only scale by whole multiples
+  --fullscreen           start in fullscreen, F11 toggles
+  --screenshot-dir dir   where F12 saves screenshots (default: screenshots)
+  --screenshot-scale n   screenshot size in pixels per CHIP-8 pixel (default: 1)
+  --screenshot-after n   save a screenshot once n frames have run
+  --record file          record gameplay to a .gif or .y4m file, or - for y4m on stdout
+  --record-start n       start the recording after n frames
+  --record-stop n        stop the recording after n frames
+  --record-scale n       recording size in pixels per CHIP-8 pixel (default: 4)
+  --record-audio file    write the buzzer to a WAV file";
+
+pub struct Options {
+    pub rom: String,
+    pub config: Option<String>,
+    pub layout: Option<String>,
+    pub platform: Option<String>,
+    pub ipf: Option<u32>,
+    pub timing: Option<Timing>,
+    pub engine: Option<Engine>,
+    pub cdp1802: Option<Cdp1802Mode>,
+    pub vip_monitor: Option<String>,
+    pub vip_interpreter: Option<String>,
+    pub load_address: Option<u16>,
+    pub font: Option<Font>,
+    pub font_address: Option<u16>,
+    pub database: Option<String>,
+    pub palette: Option<String>,
+    pub persistence: Option<u8>,
+    pub filters: Vec<String>,
+    pub scale: Option<u32>,
+    pub integer_scale: bool,
+    pub fullscreen: bool,
+    pub screenshot_dir: Option<String>,
+    pub screenshot_scale: Option<usize>,
+    pub screenshot_after: Option<u64>,
+    pub record: Option<String>,
+    pub record_start: u64,
+    pub record_stop: Option<u64>,
+    pub record_scale: Option<usize>,
+    pub record_audio: Option<String>,
+}
+
+// the transpile command's arguments
+pub struct TranspileOptions {
+    pub rom: String,
+    pub output: Option<String>,
+    pub load_address: Option<u16>,
+}
+
+impl TranspileOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut rom = None;
+        let mut output = None;
+        let mut load_address = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--load-address" => load_address = Some(Options::address(arg, args.next())?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+                _ if rom.is_none() => rom = Some(arg.clone()),
+                _ if output.is_none() => output = Some(arg.clone()),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        Ok(Self {
+            rom: rom.ok_or("Missing rom file")?,
+            output,
+            load_address,
+        })
+    }
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut rom = None;
+        let mut config = None;
+        let mut layout = None;
+        let mut platform = None;
+        let mut ipf = None;
+        let mut timing = None;
+        let mut engine = None;
+        let mut cdp1802 = None;
+        let mut vip_monitor = None;
+        let mut vip_interpreter = None;
+        let mut load_address = None;
+        let mut font = None;
+        let mut font_address = None;
+        let mut database = None;
+        let mut palette = None;
+        let mut persistence = None;
+        let mut filters = vec![];
+        let mut scale = None;
+        let mut integer_scale = false;
+        let mut fullscreen = false;
+        let mut screenshot_dir = None;
+        let mut screenshot_scale = None;
+        let mut screenshot_after = None;
+        let mut record = None;
+        let mut record_start = 0;
+        let mut record_stop = None;
+        let mut record_scale = None;
+        let mut record_audio = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => config = Some(Self::value(arg, args.next())?),
+                "--layout" => layout = Some(Self::value(arg, args.next())?),
+                "--platform" => platform = Some(Self::value(arg, args.next())?),
+                "--ipf" => ipf = Some(Self::number(arg, args.next())?),
+                "--timing" => timing = Some(Self::value(arg, args.next())?.parse()?),
+                "--engine" => engine = Some(Self::value(arg, args.next())?.parse()?),
+                "--cdp1802" => cdp1802 = Some(Self::value(arg, args.next())?.parse()?),
+                "--vip-monitor" => vip_monitor = Some(Self::value(arg, args.next())?),
+                "--vip-interpreter" => vip_interpreter = Some(Self::value(arg, args.next())?),
+                "--load-address" => load_address = Some(Self::address(arg, args.next())?),
+                "--font" => font = Some(Self::value(arg, args.next())?.parse()?),
+                "--font-address" => font_address = Some(Self::address(arg, args.next())?),
+                "--database" => database = Some(Self::value(arg, args.next())?),
+                "--palette" => palette = Some(Self::value(arg, args.next())?),
+                "--persistence" => persistence = Some(Self::number(arg, args.next())?),
+                "--filter" => filters.extend(
+                    Self::value(arg, args.next())?
+                        .split(',')
+                        .map(|name| name.trim().to_string()),
+                ),
+                "--scale" => scale = Some(Self::number(arg, args.next())?),
+                "--integer-scale" => integer_scale = true,
+                "--fullscreen" => fullscreen = true,
+                "--screenshot-dir" => screenshot_dir = Some(Self::value(arg, args.next())?),
+                "--screenshot-scale" => screenshot_scale = Some(Self::number(arg, args.next())?),
+                "--screenshot-after" => screenshot_after = Some(Self::number(arg, args.next())?),
+                "--record" => record = Some(Self::value(arg, args.next())?),
+                "--record-start" => record_start = Self::number(arg, args.next())?,
+                "--record-stop" => record_stop = Some(Self::number(arg, args.next())?),
+                "--record-scale" => record_scale = Some(Self::number(arg, args.next())?),
+                "--record-audio" => record_audio = Some(Self::value(arg, args.next())?),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+                _ if rom.is_none() => rom = Some(arg.clone()),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        Ok(Self {
+            rom: rom.ok_or("Missing rom file")?,
+            config,
+            layout,
+            platform,
+            ipf,
+            timing,
+            engine,
+            cdp1802,
+            vip_monitor,
+            vip_interpreter,
+            load_address,
+            font,
+            font_address,
+            database,
+            palette,
+            persistence,
+            filters,
+            scale,
+            integer_scale,
+            fullscreen,
+            screenshot_dir,
+            screenshot_scale,
+            screenshot_after,
+            record,
+            record_start,
+            record_stop,
+            record_scale,
+            record_audio,
+        })
+    }
+
+    fn value(flag: &str, value: Option<&String>) -> Result<String, String> {
+        value.cloned().ok_or(format!("Missing value for {}", flag))
+    }
+
+    // hex with a 0x prefix, otherwise decimal
+    fn address(flag: &str, value: Option<&String>) -> Result<u16, String> {
+        let value = Self::value(flag, value)?;
+        match value.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        }
+        .ok_or(format!("Invalid value for {}: {}", flag, value))
+    }
+
+    fn number<T: FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+        let value = Self::value(flag, value)?;
+        value
+            .parse()
+            .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+    }
+}
@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::Path;
+
+pub type Color = [u8; 3];
+
+// colors are indexed by pixel value: background, foreground (plane 1),
+// then the XO-CHIP plane 2 and "both planes" colors
+#[derive(Clone)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [Color; 4],
+}
+
+const THEMES: [(&str, [Color; 4]); 6] = [
+    (
+        "classic",
+        [
+            [0x00, 0x00, 0x00],
+            [0xFF, 0xFF, 0xFF],
+            [0xAA, 0xAA, 0xAA],
+            [0x55, 0x55, 0x55],
+        ],
+    ),
+    (
+        "green",
+        [
+            [0x0A, 0x1A, 0x0A],
+            [0x33, 0xFF, 0x66],
+            [0x1E, 0x99, 0x3D],
+            [0x99, 0xFF, 0xB3],
+        ],
+    ),
+    (
+        "amber",
+        [
+            [0x1A, 0x0F, 0x00],
+            [0xFF, 0xB0, 0x00],
+            [0x99, 0x6A, 0x00],
+            [0xFF, 0xD8, 0x80],
+        ],
+    ),
+    (
+        "lcd",
+        [
+            [0x9B, 0xBC, 0x0F],
+            [0x0F, 0x38, 0x0F],
+            [0x30, 0x62, 0x30],
+            [0x8B, 0xAC, 0x0F],
+        ],
+    ),
+    (
+        "blue",
+        [
+            [0x00, 0x10, 0x30],
+            [0x80, 0xC0, 0xFF],
+            [0x30, 0x60, 0xC0],
+            [0xFF, 0xFF, 0xFF],
+        ],
+    ),
+    (
+        "octo",
+        [
+            [0x99, 0x66, 0x00],
+            [0xFF, 0xCC, 0x00],
+            [0xFF, 0x66, 0x00],
+            [0x66, 0x22, 0x00],
+        ],
+    ),
+];
+
+impl Palette {
+    pub fn builtin() -> Vec<Palette> {
+        THEMES
+            .iter()
+            .map(|(name, colors)| Palette {
+                name: name.to_string(),
+                colors: *colors,
+            })
+            .collect()
+    }
+
+    // index of the named palette in the list, loading it from a file if it isn't there
+    pub fn select(palettes: &mut Vec<Palette>, name: &str) -> Result<usize, String> {
+        match palettes
+            .iter()
+            .position(|palette| palette.name.eq_ignore_ascii_case(name))
+        {
+            Some(i) => Ok(i),
+            None => {
+                palettes.push(Self::load(name)?);
+                Ok(palettes.len() - 1)
+            }
+        }
+    }
+
+    pub fn load(filename: &str) -> Result<Palette, String> {
+        let text = fs::read_to_string(filename)
+            .map_err(|e| format!("Couldn't read palette {}: {}", filename, e))?;
+        let name = Path::new(filename)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or(filename);
+
+        Self::parse(name, &text).map_err(|e| format!("Invalid palette {}: {}", filename, e))
+    }
+
+    // palette files list 2 to 4 hex colors (RRGGBB, optionally starting with #) separated
+    // by whitespace, in pixel order; anything after a ; is a comment
+    pub fn parse(name: &str, text: &str) -> Result<Palette, String> {
+        let mut colors = vec![];
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("");
+            for word in line.split_whitespace() {
+                colors.push(parse_color(word)?);
+            }
+        }
+
+        if colors.len() < 2 || colors.len() > 4 {
+            return Err(format!("expected 2 to 4 colors, found {}", colors.len()));
+        }
+
+        // two-color palettes get their plane colors blended from background and foreground
+        let background = colors[0];
+        let foreground = colors[1];
+        let plane2 = *colors
+            .get(2)
+            .unwrap_or(&blend(background, foreground, 2, 3));
+        let both = *colors
+            .get(3)
+            .unwrap_or(&blend(background, foreground, 1, 3));
+
+        Ok(Palette {
+            name: name.to_string(),
+            colors: [background, foreground, plane2, both],
+        })
+    }
+
+    pub fn color(&self, pixel: u8) -> Color {
+        self.colors[(pixel & 0x3) as usize]
+    }
+}
+
+pub fn parse_color(text: &str) -> Result<Color, String> {
+    let hex = text.trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or(format!("bad color: {}", text))?;
+
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+// mixes `num`/`den` of the way from a to b
+pub fn blend(a: Color, b: Color, num: u16, den: u16) -> Color {
+    let mix = |i: usize| ((a[i] as u16 * (den - num) + b[i] as u16 * num) / den) as u8;
+    [mix(0), mix(1), mix(2)]
+}
@@ -0,0 +1,161 @@
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+use super::font::Font;
+
+// behaviours that differ between CHIP-8 interpreters, named as in the
+// chip-8-community database so profiles can be read straight from it
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VX in place instead of shifting VY into VX
+    pub shift: bool,
+    // FX55/FX65 leave I at I + X instead of I + X + 1
+    pub memory_increment_by_x: bool,
+    // FX55/FX65 don't change I at all
+    pub memory_leave_i_unchanged: bool,
+    // BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump: bool,
+    // 8XY1/8XY2/8XY3 reset VF to 0
+    pub logic: bool,
+    // DXYN waits for the next vertical blank before drawing
+    pub vblank: bool,
+    // the edges sprites wrap around instead of being clipped at
+    pub wrap: Wrap,
+}
+
+// per axis, whether sprites drawn across the screen's edge come back in on the other side;
+// the database's `wrap` is true or false for both, "horizontal" or "vertical" picks one
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(try_from = "WrapSetting")]
+pub struct Wrap {
+    pub x: bool,
+    pub y: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WrapSetting {
+    Both(bool),
+    Axis(String),
+}
+
+impl TryFrom<WrapSetting> for Wrap {
+    type Error = String;
+
+    fn try_from(setting: WrapSetting) -> Result<Self, Self::Error> {
+        match setting {
+            WrapSetting::Both(wrap) => Ok(Wrap { x: wrap, y: wrap }),
+            WrapSetting::Axis(axis) => match axis.as_str() {
+                "horizontal" => Ok(Wrap { x: true, y: false }),
+                "vertical" => Ok(Wrap { x: false, y: true }),
+                _ => Err(format!(
+                    "Unknown wrap: {} (use true, false, horizontal or vertical)",
+                    axis
+                )),
+            },
+        }
+    }
+}
+
+// what this emulator did before quirks could be chosen, kept for roms nothing is known about
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            jump: false,
+            logic: false,
+            vblank: false,
+            wrap: Wrap::default(),
+        }
+    }
+}
+
+// quirks to change on top of a platform's, from a database entry or the user's config
+#[derive(Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QuirkOverrides {
+    pub shift: Option<bool>,
+    #[serde(alias = "memory_increment_by_x")]
+    pub memory_increment_by_x: Option<bool>,
+    #[serde(alias = "memory_leave_i_unchanged")]
+    pub memory_leave_i_unchanged: Option<bool>,
+    pub jump: Option<bool>,
+    pub logic: Option<bool>,
+    pub vblank: Option<bool>,
+    pub wrap: Option<Wrap>,
+}
+
+impl QuirkOverrides {
+    pub fn merge(&self, other: &QuirkOverrides) -> QuirkOverrides {
+        QuirkOverrides {
+            shift: other.shift.or(self.shift),
+            memory_increment_by_x: other.memory_increment_by_x.or(self.memory_increment_by_x),
+            memory_leave_i_unchanged: other
+                .memory_leave_i_unchanged
+                .or(self.memory_leave_i_unchanged),
+            jump: other.jump.or(self.jump),
+            logic: other.logic.or(self.logic),
+            vblank: other.vblank.or(self.vblank),
+            wrap: other.wrap.or(self.wrap),
+        }
+    }
+}
+
+impl Quirks {
+    pub fn apply(&mut self, overrides: &QuirkOverrides) {
+        let set = |quirk: &mut bool, value: Option<bool>| {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        };
+        set(&mut self.shift, overrides.shift);
+        set(
+            &mut self.memory_increment_by_x,
+            overrides.memory_increment_by_x,
+        );
+        set(
+            &mut self.memory_leave_i_unchanged,
+            overrides.memory_leave_i_unchanged,
+        );
+        set(&mut self.jump, overrides.jump);
+        set(&mut self.logic, overrides.logic);
+        set(&mut self.vblank, overrides.vblank);
+        if let Some(wrap) = overrides.wrap {
+            self.wrap = wrap;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Platform {
+    pub id: String,
+    pub name: String,
+    // "64x32" and so on, the first being the one programs start in
+    #[serde(default)]
+    pub display_resolutions: Vec<String>,
+    #[serde(default)]
+    pub default_tickrate: Option<u32>,
+    #[serde(default)]
+    pub quirks: Quirks,
+
+    // not part of the community schema: where programs are loaded and start (0x200 when
+    // missing), and the hex digit font and where it sits in memory (Octo's at 0x000 when
+    // missing)
+    #[serde(default)]
+    pub load_address: Option<u16>,
+    #[serde(default)]
+    pub font: Option<Font>,
+    #[serde(default)]
+    pub font_address: Option<u16>,
+}
+
+impl Platform {
+    pub fn resolution(&self) -> Option<(usize, usize)> {
+        let (width, height) = self.display_resolutions.first()?.split_once('x')?;
+        Some((width.parse().ok()?, height.parse().ok()?))
+    }
+}
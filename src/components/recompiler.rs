@@ -0,0 +1,256 @@
+use serde::Deserialize;
+use std::str::FromStr;
+
+use super::constants::MEMORY_SIZE;
+
+// the longest run of instructions decoded into one block
+const MAX_BLOCK_LENGTH: usize = 64;
+
+// how many ops may pile up in the cache, counting those of dropped blocks
+const MAX_OPS: usize = 0x10000;
+
+// how instructions are executed
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    // each instruction decoded as it runs
+    Interpreter,
+    // straight-line code decoded once into blocks of micro-ops and run from a cache
+    Blocks,
+    // blocks, checked instruction by instruction against the interpreter
+    Lockstep,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "interpreter" => Ok(Engine::Interpreter),
+            "blocks" => Ok(Engine::Blocks),
+            "lockstep" => Ok(Engine::Lockstep),
+            _ => Err(format!(
+                "Unknown engine: {} (use interpreter, blocks or lockstep)",
+                name
+            )),
+        }
+    }
+}
+
+// an instruction with its operands already pulled out; anything not listed runs through the
+// interpreter as Interpret
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    // 6XNN, 7XNN
+    Load(usize, u8),
+    Add(usize, u8),
+    // 8XY0 to 8XYE
+    Copy(usize, usize),
+    Or(usize, usize),
+    And(usize, usize),
+    Xor(usize, usize),
+    AddV(usize, usize),
+    Sub(usize, usize),
+    SubN(usize, usize),
+    ShiftRight(usize, usize),
+    ShiftLeft(usize, usize),
+    // ANNN, FX1E, CXNN, FX29
+    SetI(u16),
+    AddI(usize),
+    Random(usize, u8),
+    Font(usize),
+    // FX07, FX15, FX18
+    GetDelay(usize),
+    SetDelay(usize),
+    SetSound(usize),
+    // 1NNN, 2NNN, 00EE, the register skips and EX9E, EXA1
+    Jump(u16),
+    Call(u16),
+    Return,
+    SkipEq(usize, u8),
+    SkipNe(usize, u8),
+    SkipEqV(usize, usize),
+    SkipNeV(usize, usize),
+    SkipKey(usize),
+    SkipNoKey(usize),
+    // run by the interpreter: Store for the instructions that write memory the block cache
+    // may hold code from (5XY2, FX33, FX55 and 0NNN, which may be a VIP routine)
+    Interpret,
+    Store,
+}
+
+impl Op {
+    pub fn decode(opcode: u16) -> Self {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+        match opcode & 0xF000 {
+            0x0000 if opcode == 0x00EE => Op::Return,
+            0x0000 if !matches!(opcode, 0x00C0..=0x00DF | 0x00E0 | 0x00FB..=0x00FF) => Op::Store,
+            0x5000 if opcode & 0x000F == 0x2 => Op::Store,
+            0x1000 => Op::Jump(nnn),
+            0x2000 => Op::Call(nnn),
+            0x3000 => Op::SkipEq(x, nn),
+            0x4000 => Op::SkipNe(x, nn),
+            0x5000 if opcode & 0x000F == 0 => Op::SkipEqV(x, y),
+            0x6000 => Op::Load(x, nn),
+            0x7000 => Op::Add(x, nn),
+            0x8000 => match opcode & 0x000F {
+                0x0 => Op::Copy(x, y),
+                0x1 => Op::Or(x, y),
+                0x2 => Op::And(x, y),
+                0x3 => Op::Xor(x, y),
+                0x4 => Op::AddV(x, y),
+                0x5 => Op::Sub(x, y),
+                0x6 => Op::ShiftRight(x, y),
+                0x7 => Op::SubN(x, y),
+                0xE => Op::ShiftLeft(x, y),
+                _ => Op::Interpret,
+            },
+            0x9000 if opcode & 0x000F == 0 => Op::SkipNeV(x, y),
+            0xA000 => Op::SetI(nnn),
+            0xE000 if nn == 0x9E => Op::SkipKey(x),
+            0xE000 if nn == 0xA1 => Op::SkipNoKey(x),
+            0xC000 => Op::Random(x, nn),
+            0xF000 => match nn {
+                0x33 | 0x55 => Op::Store,
+                0x07 => Op::GetDelay(x),
+                0x15 => Op::SetDelay(x),
+                0x18 => Op::SetSound(x),
+                0x1E => Op::AddI(x),
+                0x29 => Op::Font(x),
+                _ => Op::Interpret,
+            },
+            _ => Op::Interpret,
+        }
+    }
+}
+
+// whether the instruction never goes on to the next one, may write memory the rest of the
+// block was decoded from, or is F000 NNNN with its extra two bytes. skips, key checks and
+// waits don't end a block: running one stops at the first op that leaves pc anywhere but
+// the next instruction
+fn ends_block(opcode: u16, op: Op) -> bool {
+    match op {
+        Op::Jump(_) | Op::Call(_) | Op::Return | Op::Store => true,
+        Op::Interpret => opcode & 0xF000 == 0xB000 || opcode == 0x00FD || opcode == 0xF000,
+        _ => false,
+    }
+}
+
+// the straight-line run of instructions from `start`, ending with the first that may not be
+// followed by the next one
+pub fn decode_block(memory: &[u8], start: u16) -> Vec<Op> {
+    let mut ops = vec![];
+    let mut address = start as usize;
+    while ops.len() < MAX_BLOCK_LENGTH && address + 1 < memory.len() {
+        let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+        let op = Op::decode(opcode);
+        ops.push(op);
+        address += 2;
+        if ends_block(opcode, op) {
+            break;
+        }
+    }
+    // the last byte of memory: let the interpreter deal with it
+    if ops.is_empty() {
+        ops.push(Op::Interpret);
+    }
+    ops
+}
+
+// where a block's ops are in the cache, and the memory they were decoded from
+#[derive(Clone, Copy)]
+pub struct Block {
+    pub start: u16,
+    pub end: usize,
+    // the index of its first op, and how many there are
+    pub first: usize,
+    pub length: usize,
+}
+
+// decoded blocks by start address, their ops kept one after another in a single list so
+// running them is an index rather than a lookup per instruction
+pub struct BlockCache {
+    blocks: Vec<Option<Block>>,
+    ops: Vec<Op>,
+    // the index of the next op of the current block and the address it is for, while
+    // running one instruction at a time
+    cursor: Option<(usize, usize, u16)>,
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        Self {
+            blocks: vec![None; MEMORY_SIZE],
+            ops: vec![],
+            cursor: None,
+        }
+    }
+}
+
+impl BlockCache {
+    // the block starting at pc, decoded if it's new
+    pub fn block(&mut self, pc: u16, memory: &[u8]) -> Block {
+        if let Some(block) = self.blocks[pc as usize] {
+            return block;
+        }
+        // dropped blocks leave their ops behind; start over once there are too many
+        if self.ops.len() > MAX_OPS {
+            self.clear();
+        }
+
+        let ops = decode_block(memory, pc);
+        let block = Block {
+            start: pc,
+            end: (pc as usize + 2 * ops.len()).min(memory.len()),
+            first: self.ops.len(),
+            length: ops.len(),
+        };
+        self.ops.extend(ops);
+        self.blocks[pc as usize] = Some(block);
+        block
+    }
+
+    pub fn op(&self, index: usize) -> Op {
+        self.ops[index]
+    }
+
+    // the op for the instruction at pc: the next one of the current block when execution went
+    // straight on, otherwise the first of the block starting at pc
+    pub fn next(&mut self, pc: u16, memory: &[u8]) -> Op {
+        let (index, end) = match self.cursor {
+            Some((index, end, next)) if next == pc => (index, end),
+            _ => {
+                let block = self.block(pc, memory);
+                (block.first, block.first + block.length)
+            }
+        };
+        self.cursor = if index + 1 < end {
+            Some((index + 1, end, pc.wrapping_add(2)))
+        } else {
+            None
+        };
+        self.ops[index]
+    }
+
+    // drops the blocks decoded from bytes in `start..end`, after a write there
+    pub fn invalidate(&mut self, start: usize, end: usize) {
+        // no block is longer than MAX_BLOCK_LENGTH instructions, so only those starting that
+        // far before the write can reach it
+        let first = start.saturating_sub(MAX_BLOCK_LENGTH * 2);
+        for slot in &mut self.blocks[first..end.min(MEMORY_SIZE)] {
+            if slot.is_some_and(|block| start < block.end) {
+                *slot = None;
+                self.cursor = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.iter_mut().for_each(|slot| *slot = None);
+        self.ops.clear();
+        self.cursor = None;
+    }
+}
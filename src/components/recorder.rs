@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::constants::FRAME_RATE;
+use super::display::Display;
+
+// records gameplay one emulated frame at a time, so the output plays back at the
+// speed of the emulated machine however fast or slow the host ran it
+pub enum Recorder {
+    Gif(GifRecorder),
+    Y4m(Y4mRecorder),
+}
+
+impl Recorder {
+    // picks the format from the extension; "-" streams y4m to stdout for piping into an encoder
+    pub fn create(filename: &str, width: usize, height: usize) -> Result<Self, String> {
+        let error = |e: io::Error| format!("Couldn't create {}: {}", filename, e);
+        if filename == "-" {
+            let out: Box<dyn Write> = Box::new(io::stdout());
+            return Ok(Recorder::Y4m(
+                Y4mRecorder::new(out, width, height).map_err(error)?,
+            ));
+        }
+
+        // check the format first so a bad name doesn't leave an empty file behind
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        if extension != "gif" && extension != "y4m" {
+            return Err(format!("Unknown recording format: {}", filename));
+        }
+
+        let out = BufWriter::new(File::create(filename).map_err(error)?);
+        if extension == "gif" {
+            Ok(Recorder::Gif(GifRecorder::new(
+                Box::new(out),
+                width,
+                height,
+            )?))
+        } else {
+            Ok(Recorder::Y4m(
+                Y4mRecorder::new(Box::new(out), width, height).map_err(error)?,
+            ))
+        }
+    }
+
+    pub fn write_frame(&mut self, display: &Display) -> Result<(), String> {
+        match self {
+            Recorder::Gif(gif) => gif.write_frame(display),
+            Recorder::Y4m(y4m) => y4m.write_frame(display).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            Recorder::Gif(gif) => gif.finish(),
+            Recorder::Y4m(y4m) => y4m.finish().map_err(|e| e.to_string()),
+        }
+    }
+}
+
+pub struct GifRecorder {
+    encoder: gif::Encoder<Box<dyn Write>>,
+    width: usize,
+    height: usize,
+    // identical frames are merged, so the last image is held until it changes
+    pending: Option<Vec<u8>>,
+    pending_since: u64,
+    frames: u64,
+}
+
+impl GifRecorder {
+    fn new(out: Box<dyn Write>, width: usize, height: usize) -> Result<Self, String> {
+        let mut encoder =
+            gif::Encoder::new(out, width as u16, height as u16, &[]).map_err(|e| e.to_string())?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            pending: None,
+            pending_since: 0,
+            frames: 0,
+        })
+    }
+
+    fn write_frame(&mut self, display: &Display) -> Result<(), String> {
+        let rgb = resample(
+            &display.rgb,
+            (display.width, display.height),
+            (self.width, self.height),
+        );
+        if self.pending.as_ref() != Some(&rgb) {
+            // viewers slow down frames shorter than 2/100 s, so a frame that changes sooner is
+            // replaced rather than kept, capping the gif at 50 fps
+            if centiseconds(self.frames) - centiseconds(self.pending_since) >= 2 {
+                self.flush()?;
+                self.pending_since = self.frames;
+            }
+            self.pending = Some(rgb);
+        }
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        let rgb = match self.pending.take() {
+            Some(rgb) => rgb,
+            None => return Ok(()),
+        };
+
+        // CHIP-8 screens only use a handful of colors, so an exact palette nearly always fits
+        let mut colors: HashMap<[u8; 3], u8> = HashMap::new();
+        let mut palette = vec![];
+        let mut pixels = Vec::with_capacity(self.width * self.height);
+        for pixel in rgb.chunks(3) {
+            let color = [pixel[0], pixel[1], pixel[2]];
+            let index = match colors.get(&color) {
+                Some(index) => *index,
+                None if colors.len() < 256 => {
+                    let index = colors.len() as u8;
+                    colors.insert(color, index);
+                    palette.extend_from_slice(&color);
+                    index
+                }
+                None => break,
+            };
+            pixels.push(index);
+        }
+
+        let mut frame = if pixels.len() == self.width * self.height {
+            gif::Frame::from_palette_pixels(
+                self.width as u16,
+                self.height as u16,
+                &pixels,
+                &palette,
+                None,
+            )
+        } else {
+            gif::Frame::from_rgb_speed(self.width as u16, self.height as u16, &rgb, 10)
+        };
+
+        let delay = centiseconds(self.frames) - centiseconds(self.pending_since);
+        frame.delay = delay.min(u16::MAX as u64) as u16;
+
+        self.encoder.write_frame(&frame).map_err(|e| e.to_string())
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        self.flush()
+    }
+}
+
+// gif delays are in hundredths of a second, so each frame's end time is rounded to keep
+// the total in step with the 60 Hz clock
+fn centiseconds(frame: u64) -> u64 {
+    frame * 100 / FRAME_RATE as u64
+}
+
+// uncompressed YUV4MPEG2 stream, 4:4:4 at 60 fps
+pub struct Y4mRecorder {
+    out: Box<dyn Write>,
+    width: usize,
+    height: usize,
+}
+
+impl Y4mRecorder {
+    fn new(mut out: Box<dyn Write>, width: usize, height: usize) -> io::Result<Self> {
+        writeln!(
+            out,
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+            width, height, FRAME_RATE
+        )?;
+
+        Ok(Self { out, width, height })
+    }
+
+    fn write_frame(&mut self, display: &Display) -> io::Result<()> {
+        let rgb = resample(
+            &display.rgb,
+            (display.width, display.height),
+            (self.width, self.height),
+        );
+
+        let size = self.width * self.height;
+        let mut planes = vec![0; size * 3];
+        for (i, pixel) in rgb.chunks(3).enumerate() {
+            let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+            // BT.601 studio range
+            planes[i] = (16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8;
+            planes[size + i] = (128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8;
+            planes[size * 2 + i] = (128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8;
+        }
+
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&planes)
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// nearest neighbour resize of an RGB image, so recordings keep one size even when the
+// emulated resolution changes part way through
+pub fn resample(rgb: &[u8], from: (usize, usize), to: (usize, usize)) -> Vec<u8> {
+    let (from_width, from_height) = from;
+    let (to_width, to_height) = to;
+
+    let mut out = Vec::with_capacity(to_width * to_height * 3);
+    for y in 0..to_height {
+        let row = y * from_height / to_height;
+        for x in 0..to_width {
+            let i = (row * from_width + x * from_width / to_width) * 3;
+            out.extend_from_slice(&rgb[i..i + 3]);
+        }
+    }
+    out
+}
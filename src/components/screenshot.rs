@@ -0,0 +1,87 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::display::{upscale, Display};
+
+// saves what is on screen, palette and all, scaled up by a whole factor (1 for native size)
+pub fn save_screenshot(
+    display: &Display,
+    scale: usize,
+    directory: &str,
+    rom: &str,
+) -> Result<PathBuf, String> {
+    let filename = timestamped_path(directory, rom, "png")?;
+    let rgb = upscale(&display.rgb, display.width, display.height, scale);
+    save_png(
+        &filename,
+        &rgb,
+        display.width * scale,
+        display.height * scale,
+    )?;
+
+    Ok(filename)
+}
+
+pub fn save_png(filename: &Path, rgb: &[u8], width: usize, height: usize) -> Result<(), String> {
+    let file = File::create(filename)
+        .map_err(|e| format!("Couldn't create {}: {}", filename.display(), e))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgb))
+        .map_err(|e| format!("Couldn't write {}: {}", filename.display(), e))
+}
+
+// a new file in `directory` named after the rom and the current time,
+// e.g. screenshots/pong2-20240131-154502-123.png
+pub fn timestamped_path(directory: &str, rom: &str, extension: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(directory)
+        .map_err(|e| format!("Couldn't create directory {}: {}", directory, e))?;
+
+    let stem = Path::new(rom)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("chip8");
+
+    Ok(Path::new(directory).join(format!("{}-{}.{}", stem, timestamp(), extension)))
+}
+
+// local calendar maths would need a timezone database, so names use UTC
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        now.subsec_millis()
+    )
+}
+
+// days since 1970-01-01 to a (year, month, day) date, after Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use std::str::FromStr;
+
+// the VIP's 1802 runs at 1.76 MHz, 8 clocks to a machine cycle, giving 3668 machine cycles
+// to each 60 Hz frame
+pub const VIP_CYCLES_PER_FRAME: i32 = 3668;
+
+// the display interrupt keeps the processor busy for the 128 scanlines it feeds to the
+// video chip by DMA, leaving the rest of the frame to the interpreter
+pub const VIP_DISPLAY_CYCLES: i32 = 1832;
+
+// fetching and decoding an instruction, before its own routine runs
+const VIP_FETCH_CYCLES: u32 = 40;
+
+// how the length of a frame is measured
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Timing {
+    // a fixed number of instructions per frame, however long each would take
+    Instructions,
+    // instructions charged what they cost on the COSMAC VIP, frames ending on a cycle budget
+    Vip,
+}
+
+impl FromStr for Timing {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "instructions" => Ok(Timing::Instructions),
+            "vip" => Ok(Timing::Vip),
+            _ => Err(format!(
+                "Unknown timing: {} (use instructions or vip)",
+                name
+            )),
+        }
+    }
+}
+
+// machine cycles the VIP interpreter spends on an instruction, from its fetch to the next
+// one, given VX as it was before it ran and whether a conditional skip was taken.
+// instructions the VIP doesn't have are charged like the closest one it does
+pub fn vip_cycles(opcode: u16, vx: u8, skipped: bool) -> u32 {
+    let x = ((opcode & 0x0F00) >> 8) as u32;
+    let n = (opcode & 0x000F) as u32;
+    let skip = if skipped { 4 } else { 0 };
+
+    let cycles = match opcode & 0xF000 {
+        // clearing goes over all 256 bytes of display memory
+        0x0000 if opcode == 0x00E0 => 3078,
+        0x0000 if opcode == 0x00EE => 10,
+        // scrolling and resolution switches: a machine code routine of about the same size
+        0x0000 => 3078,
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 => 10 + skip,
+        0x5000 if n == 0 => 14 + skip,
+        0x5000 => 14 + 14 * (x.max((opcode as u32 & 0x00F0) >> 4) + 1),
+        0x6000 => 6,
+        0x7000 => 10,
+        // the ALU ops run a short machine code stub built on the fly
+        0x8000 => 44,
+        0x9000 => 14 + skip,
+        0xA000 => 12,
+        // jumping to a different page costs the page fetch again
+        0xB000 => {
+            22 + if (opcode & 0x00FF) as u32 + vx as u32 > 0xFF {
+                2
+            } else {
+                0
+            }
+        }
+        0xC000 => 36,
+        // each row is shifted into place a bit at a time, and drawn over two bytes unless
+        // the sprite sits on a byte boundary
+        0xD000 => {
+            let rows = if n == 0 { 16 } else { n };
+            let shift = (vx % 8) as u32;
+            let per_row = if shift == 0 { 34 } else { 54 + 8 * shift };
+            26 + rows * per_row
+        }
+        0xE000 => 14 + skip,
+        0xF000 => match opcode & 0x00FF {
+            0x07 | 0x15 | 0x18 => 10,
+            // a pass through the keyboard scan, repeated until a key is down
+            0x0A => 20,
+            0x1E => 16,
+            0x29 => 16,
+            0x30 => 16,
+            // each hundred, ten and one is counted out by repeated subtraction
+            0x33 => 80 + 16 * ((vx / 100) + (vx / 10) % 10 + vx % 10) as u32,
+            0x55 | 0x65 | 0x75 | 0x85 => 14 + 14 * (x + 1),
+            _ => 16,
+        },
+        _ => 12,
+    };
+
+    VIP_FETCH_CYCLES + cycles
+}
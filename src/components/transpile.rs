@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::constants::MEMORY_SIZE;
+use super::cpu::Cpu;
+use super::detect::reachable;
+use super::recompiler::{decode_block, Op};
+
+// the `run` function of a translated rom: runs the block at pc, at most `limit` instructions
+// of it, and returns how many ran, 0 when nothing was translated at pc
+pub type Translated = fn(&mut Cpu, u32) -> u32;
+
+// the Rust source of a module running the code reachable in `rom`, loaded and started at
+// `address`, through the same micro-ops as the block engine. each block checks its bytes
+// are still in memory before it runs, so code the program overwrites and targets of
+// computed jumps (BNNN) are left to the interpreter
+pub fn transpile(rom: &[u8], address: u16, name: &str) -> String {
+    let mut memory = vec![0; MEMORY_SIZE];
+    let start = address as usize;
+    let length = rom.len().min(MEMORY_SIZE.saturating_sub(start));
+    memory[start..start + length].copy_from_slice(&rom[..length]);
+
+    let mut source = String::new();
+    let _ = writeln!(
+        source,
+        "// {} translated to Rust by `chip_8_rust transpile`. Load ROM at LOAD_ADDRESS and hand
+// `run` to `Cpu::set_translated`; anything not translated here runs on the interpreter
+use chip_8_rust::components::cpu::Cpu;
+use chip_8_rust::components::recompiler::Op;
+
+pub const LOAD_ADDRESS: u16 = 0x{:03X};
+
+pub const ROM: &[u8] = &[",
+        name, address
+    );
+    for line in rom.chunks(12) {
+        let bytes: Vec<String> = line.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+        let _ = writeln!(source, "    {},", bytes.join(", "));
+    }
+    let _ = writeln!(
+        source,
+        "];
+
+// runs the block at pc, at most `limit` instructions of it, and returns how many ran; 0
+// when nothing was translated at pc
+pub fn run(cpu: &mut Cpu, limit: u32) -> u32 {{
+    match cpu.pc() {{"
+    );
+
+    for leader in leaders(&memory[start..start + length], address) {
+        let ops = decode_block(&memory, leader);
+        let code = &memory[leader as usize..(leader as usize + 2 * ops.len()).min(MEMORY_SIZE)];
+        let bytes: Vec<String> = code.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+        let _ = writeln!(
+            source,
+            "        0x{:03X} => {{
+            if !cpu.code_matches(0x{:03X}, &[{}]) {{
+                return 0;
+            }}",
+            leader,
+            leader,
+            bytes.join(", ")
+        );
+
+        for (i, (op, opcode)) in ops.iter().zip(code.chunks(2)).enumerate() {
+            let ran = i + 1;
+            let _ = writeln!(
+                source,
+                "            cpu.execute(Op::{:?}); // {:02X}{:02X}",
+                op,
+                opcode[0],
+                opcode.get(1).unwrap_or(&0)
+            );
+            if ran == ops.len() {
+                break;
+            }
+            let next = leader as usize + 2 * ran;
+            let condition = if falls_through(*op) {
+                format!("limit == {}", ran)
+            } else {
+                format!("cpu.pc() != 0x{:03X} || limit == {}", next, ran)
+            };
+            let _ = writeln!(
+                source,
+                "            if {} {{
+                return {};
+            }}",
+                condition, ran
+            );
+        }
+        let _ = writeln!(source, "            {}\n        }}", ops.len());
+    }
+
+    let _ = writeln!(source, "        _ => 0,\n    }}\n}}");
+    source
+}
+
+// whether an op always goes on to the next instruction
+fn falls_through(op: Op) -> bool {
+    !matches!(
+        op,
+        Op::Jump(_)
+            | Op::Call(_)
+            | Op::Return
+            | Op::SkipEq(..)
+            | Op::SkipNe(..)
+            | Op::SkipEqV(..)
+            | Op::SkipNeV(..)
+            | Op::SkipKey(_)
+            | Op::SkipNoKey(_)
+            | Op::Interpret
+            | Op::Store
+    )
+}
+
+// the addresses translated blocks start at: the entry point, jump and call targets, and
+// wherever execution resumes after a skip, a return, a block ending on a memory write or
+// an instruction that waits (DXYN with the vblank quirk, FX0A)
+fn leaders(rom: &[u8], address: u16) -> BTreeSet<u16> {
+    let code = reachable(rom, address);
+    let mut leaders = BTreeSet::new();
+    leaders.insert(address);
+    for &at in &code {
+        let i = (at - address) as usize;
+        let opcode = (rom[i] as u16) << 8 | *rom.get(i + 1).unwrap_or(&0) as u16;
+        let next = at.wrapping_add(2);
+        match Op::decode(opcode) {
+            Op::Jump(target) => {
+                leaders.insert(target);
+            }
+            Op::Call(target) => {
+                leaders.insert(target);
+                leaders.insert(next);
+            }
+            Op::SkipEq(..)
+            | Op::SkipNe(..)
+            | Op::SkipEqV(..)
+            | Op::SkipNeV(..)
+            | Op::SkipKey(_)
+            | Op::SkipNoKey(_) => {
+                let long = rom.get(i + 2) == Some(&0xF0) && rom.get(i + 3) == Some(&0x00);
+                leaders.insert(next.wrapping_add(if long { 4 } else { 2 }));
+            }
+            Op::Store => {
+                leaders.insert(next);
+            }
+            _ if opcode == 0xF000 => {
+                leaders.insert(next.wrapping_add(2));
+            }
+            _ if opcode & 0xF000 == 0xD000 || opcode & 0xF0FF == 0xF00A => {
+                leaders.insert(at);
+            }
+            _ => {}
+        }
+    }
+    // only what the walk found is known to be code
+    leaders.retain(|leader| code.binary_search(leader).is_ok());
+    leaders
+}
@@ -0,0 +1,237 @@
+use serde::Deserialize;
+use std::str::FromStr;
+
+use super::cdp1802::{Bus, Cdp1802};
+
+// the VIP's 4 KB of RAM repeats up to 0x7FFF; the monitor ROM sits above it
+const RAM_SIZE: usize = 0x1000;
+const ROM_START: u16 = 0x8000;
+
+// where the CHIP-8 interpreter keeps things in the top page of RAM
+pub const STACK_TOP: u16 = 0x0ECF;
+pub const REGISTERS: u16 = 0x0EF0;
+pub const DISPLAY: u16 = 0x0F00;
+
+// the 1861 video chip: 262 lines of 14 machine cycles, of which 128 are shown from line
+// 80, each fed 8 bytes by DMA; it interrupts 2 lines before the first and raises EF1 for
+// the 4 lines either side of the display's start and end
+const LINES: u32 = 262;
+const LINE_CYCLES: u32 = 14;
+const FIRST_LINE: u32 = 80;
+const SHOWN_LINES: usize = 128;
+const INTERRUPT_LINE: u32 = 78;
+
+// how long a machine code routine may run before it is given up on
+const ROUTINE_CYCLES: u32 = 10_000_000;
+
+// what the emulated CDP1802 is used for
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Cdp1802Mode {
+    // 0NNN runs the machine code at NNN, everything else is interpreted as usual
+    Routines,
+    // a VIP interpreter image runs the whole program on the 1802
+    Interpreter,
+}
+
+impl FromStr for Cdp1802Mode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "routines" => Ok(Cdp1802Mode::Routines),
+            "interpreter" => Ok(Cdp1802Mode::Interpreter),
+            _ => Err(format!(
+                "Unknown CDP1802 mode: {} (use routines or interpreter)",
+                name
+            )),
+        }
+    }
+}
+
+// a COSMAC VIP around the shared CHIP-8 memory: the processor, the monitor ROM, the video
+// chip and the hex keypad
+#[derive(Clone)]
+pub struct Vip {
+    pub mode: Cdp1802Mode,
+    pub cpu: Cdp1802,
+    monitor: Vec<u8>,
+    display_on: bool,
+    key_latch: u8,
+    // the address each shown line was fed from during the last frame
+    lines: Vec<Option<u16>>,
+    // the bytes shown on each line
+    line_data: Vec<[u8; 8]>,
+}
+
+struct VipBus<'a> {
+    memory: &'a mut [u8],
+    monitor: &'a [u8],
+    keys: &'a [u8; 16],
+    display_on: &'a mut bool,
+    key_latch: &'a mut u8,
+    ef1: bool,
+}
+
+impl Bus for VipBus<'_> {
+    fn read(&mut self, address: u16) -> u8 {
+        if address >= ROM_START {
+            if self.monitor.is_empty() {
+                return 0;
+            }
+            self.monitor[(address - ROM_START) as usize % self.monitor.len()]
+        } else {
+            self.memory[address as usize % RAM_SIZE]
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address < ROM_START {
+            self.memory[address as usize % RAM_SIZE] = value;
+        }
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            // OUT 1 turns the display off, OUT 2 picks the key EF3 reports on
+            1 => *self.display_on = false,
+            2 => *self.key_latch = value & 0x0F,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        // INP 1 turns the display on
+        if port == 1 {
+            *self.display_on = true;
+        }
+        0
+    }
+
+    fn flag(&mut self, flag: u8) -> bool {
+        match flag {
+            1 => self.ef1,
+            3 => self.keys[*self.key_latch as usize] != 0,
+            _ => false,
+        }
+    }
+}
+
+impl Vip {
+    // `monitor` is the VIP's ROM, which the interpreter's display interrupt and digit font
+    // live in; without one, reads above 0x8000 give 0
+    pub fn new(mode: Cdp1802Mode, monitor: Vec<u8>) -> Self {
+        Self {
+            mode,
+            cpu: Cdp1802::new(),
+            monitor,
+            display_on: false,
+            key_latch: 0,
+            lines: vec![None; SHOWN_LINES],
+            line_data: vec![[0; 8]; SHOWN_LINES],
+        }
+    }
+
+    fn bus<'a>(
+        &'a mut self,
+        memory: &'a mut [u8],
+        keys: &'a [u8; 16],
+        ef1: bool,
+    ) -> (&'a mut Cdp1802, VipBus<'a>) {
+        (
+            &mut self.cpu,
+            VipBus {
+                memory,
+                monitor: &self.monitor,
+                keys,
+                display_on: &mut self.display_on,
+                key_latch: &mut self.key_latch,
+                ef1,
+            },
+        )
+    }
+
+    // runs the processor from its current state until it hands control back with SEP R4, the
+    // way the interpreter's 0NNN routines return; the caller sets up the registers first
+    pub fn call(&mut self, memory: &mut [u8], keys: &[u8; 16]) -> Result<(), String> {
+        let (cpu, mut bus) = self.bus(memory, keys, false);
+        let mut cycles = 0;
+        while cpu.p != 4 {
+            if cpu.idle {
+                return Err("machine code routine stopped with IDL".to_string());
+            }
+            cycles += cpu.step(&mut bus);
+            if cycles > ROUTINE_CYCLES {
+                return Err("machine code routine didn't return".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // starts an interpreter image the way the monitor runs a program: from 0x0000 on R0,
+    // with R1.1 holding the top page of RAM
+    pub fn reset(&mut self) {
+        self.cpu = Cdp1802::new();
+        self.cpu.r[1] = (RAM_SIZE as u16 - 1) & 0xFF00;
+        self.display_on = false;
+    }
+
+    // runs one 60 Hz frame of the whole machine, video chip interrupts and DMA included
+    pub fn run_frame(&mut self, memory: &mut [u8], keys: &[u8; 16]) {
+        let mut lines = vec![None; SHOWN_LINES];
+        let mut line_data = vec![[0; 8]; SHOWN_LINES];
+
+        let mut cycle = 0;
+        for line in 0..LINES {
+            let start = line * LINE_CYCLES;
+            let display_on = self.display_on;
+            let ef1 = display_on
+                && ((FIRST_LINE - 4..FIRST_LINE).contains(&line)
+                    || (FIRST_LINE + SHOWN_LINES as u32 - 4..FIRST_LINE + SHOWN_LINES as u32)
+                        .contains(&line));
+
+            let (cpu, mut bus) = self.bus(memory, keys, ef1);
+            if display_on && line == INTERRUPT_LINE {
+                cycle += cpu.interrupt();
+            }
+            let shown = line.checked_sub(FIRST_LINE).map(|line| line as usize);
+            if let Some(shown) = shown.filter(|&shown| display_on && shown < SHOWN_LINES) {
+                lines[shown] = Some(cpu.r[0]);
+                for byte in line_data[shown].iter_mut() {
+                    *byte = cpu.dma_out(&mut bus);
+                }
+                cycle += 8;
+            }
+
+            while cycle < start + LINE_CYCLES {
+                cycle += cpu.step(&mut bus);
+            }
+        }
+
+        self.lines = lines;
+        self.line_data = line_data;
+    }
+
+    // the picture of the last frame, one bit per pixel: lines fed from the same address are
+    // one row repeated, 4 times for the usual 64x32 display and twice for 64x64 ones
+    pub fn screen(&self) -> (usize, Vec<[u8; 8]>) {
+        let repeat = match self.lines[0] {
+            Some(first) => self
+                .lines
+                .iter()
+                .take_while(|&&line| line == Some(first))
+                .count(),
+            None => return (32, vec![[0; 8]; 32]),
+        };
+        let height = SHOWN_LINES / repeat.max(1);
+        let rows = (0..height)
+            .map(|row| self.line_data[row * repeat])
+            .collect();
+        (height, rows)
+    }
+
+    // the VIP's speaker sounds while Q is set
+    pub fn sound_active(&self) -> bool {
+        self.cpu.q
+    }
+}
@@ -0,0 +1,2 @@
+// the emulator's parts, also usable from other programs (e.g. to load and run roms headless)
+pub mod components;
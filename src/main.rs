@@ -1,155 +1,724 @@
 extern crate sdl2;
 
-mod components;
-
-use sdl2::audio::{AudioCVT, AudioSpecDesired, AudioSpecWAV};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::PixelFormatEnum;
+use sdl2::audio::AudioSpecDesired;
+use sdl2::controller::Button;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use std::{env, error, path};
+use sdl2::video::FullscreenType;
+use std::time::{Duration, Instant};
+use std::{env, error, fs, thread};
+
+use chip_8_rust::components::audio::{Sound, Tone, WavWriter, Waveform, SAMPLE_RATE};
+use chip_8_rust::components::config::Config;
+use chip_8_rust::components::constants::{DISPLAY_MODIFIER, FRAME_RATE, INSTRUCTIONS_PER_FRAME};
+use chip_8_rust::components::controller::ControllerBindings;
+use chip_8_rust::components::cpu::Cpu;
+use chip_8_rust::components::database::Database;
+use chip_8_rust::components::detect::detect;
+use chip_8_rust::components::display::{letterbox, Display};
+use chip_8_rust::components::filters::Filters;
+use chip_8_rust::components::keypad::KeyBindings;
+use chip_8_rust::components::options::{Options, TranspileOptions, USAGE};
+use chip_8_rust::components::palette::{parse_color, Palette};
+use chip_8_rust::components::recorder::Recorder;
+use chip_8_rust::components::screenshot::{save_screenshot, timestamped_path};
+use chip_8_rust::components::timing::Timing;
+use chip_8_rust::components::transpile::transpile;
+use chip_8_rust::components::vip::{Cdp1802Mode, Vip};
+
+#[derive(Clone, Copy, PartialEq)]
+enum HostKey {
+    Key(Keycode),
+    Scan(Scancode),
+}
+
+// resolves the keypad's host key names, "scan:" forces a name to be read as a scancode
+fn host_keys(bindings: &KeyBindings) -> Result<Vec<(HostKey, u8)>, String> {
+    let mut keys = vec![];
+    for (name, key) in bindings.keys() {
+        let host = match name.strip_prefix("scan:") {
+            Some(name) => Scancode::from_name(name).map(HostKey::Scan),
+            None => Keycode::from_name(name).map(HostKey::Key),
+        };
+        keys.push((host.ok_or(format!("Unknown host key: {}", name))?, *key));
+    }
+    Ok(keys)
+}
+
+// checks the controller's button names against the ones SDL knows
+fn check_controls(bindings: &ControllerBindings) -> Result<(), String> {
+    match bindings
+        .buttons()
+        .find(|name| Button::from_string(name).is_none())
+    {
+        Some(name) => Err(format!("Unknown controller input: {}", name)),
+        None => Ok(()),
+    }
+}
+
+fn press_keys(
+    cpu: &mut Cpu,
+    host_keys: &[(HostKey, u8)],
+    keycode: Option<Keycode>,
+    scancode: Option<Scancode>,
+    state: u8,
+) {
+    for (host, key) in host_keys {
+        let hit = match host {
+            HostKey::Key(host) => keycode == Some(*host),
+            HostKey::Scan(host) => scancode == Some(*host),
+        };
+        if hit {
+            cpu.keypad.press(*key, state);
+        }
+    }
+}
 
-use components::audio::Sound;
-use components::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
-use components::cpu::Cpu;
+// writes a rom's code out as a Rust module
+fn transpile_rom(args: &[String]) -> Result<(), Box<dyn error::Error>> {
+    let options = match TranspileOptions::parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", USAGE);
+            return Err(e.into());
+        }
+    };
+
+    let mut cpu = Cpu::new();
+    if let Some(address) = options.load_address {
+        cpu.set_load_address(address);
+    }
+    if let Err(e) = cpu.load_application(&options.rom) {
+        return Err(format!("Failed to load rom: {}", e).into());
+    }
+    let name = std::path::Path::new(&options.rom)
+        .file_name()
+        .map_or(options.rom.clone(), |name| {
+            name.to_string_lossy().into_owned()
+        });
+    let source = transpile(cpu.program(), cpu.pc(), &name);
+    match options.output {
+        Some(output) => fs::write(output, source)?,
+        None => print!("{}", source),
+    }
+    Ok(())
+}
 
 fn main() -> Result<(), Box<dyn error::Error>> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("syntax: chip_8_rust [rom_file]");
+    if args.get(1).map(String::as_str) == Some("transpile") {
+        return transpile_rom(&args[2..]);
     }
+    let options = match Options::parse(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}", e);
+            println!("{}", USAGE);
+            return Ok(());
+        }
+    };
 
+    let config = Config::load_or_default(options.config.as_deref())?;
     let mut cpu = Cpu::new();
-    if !cpu.load_application(&args[1]) {
-        println!("Failed to load rom");
+    // the rom's sha1 isn't known yet, so only settings by file name apply here; the fonts go
+    // in first so the program can't end up under them
+    let by_name = config.emulation_for(&options.rom, "");
+    let load_address = options.load_address.or(by_name.load_address);
+    if let Some(address) = load_address {
+        cpu.set_load_address(address);
+    }
+    let font = options.font.or(by_name.font);
+    let font_address = options.font_address.or(by_name.font_address);
+    if font.is_some() || font_address.is_some() {
+        cpu.set_font(font.unwrap_or_default(), font_address.unwrap_or(0))?;
+    }
+    if let Err(e) = cpu.load_application(&options.rom) {
+        eprintln!("Failed to load rom: {}", e);
         return Ok(());
     }
+    if let (Some(origin), Some(address)) = (cpu.rom_origin(), load_address) {
+        if origin != address {
+            eprintln!(
+                "Failed to load rom: it says it loads at {:#05X}, not {:#05X}",
+                origin, address
+            );
+            return Ok(());
+        }
+    }
+    let sha1 = cpu.rom_sha1.clone();
+
+    // what the database knows about the rom, with the user's settings on top
+    let emulation = config.emulation_for(&options.rom, &sha1);
+    let mut database = Database::bundled();
+    if let Some(directory) = options.database.as_ref().or(emulation.database.as_ref()) {
+        database.merge(Database::load(directory)?);
+    }
+    let mut profile = database.profile(
+        &sha1,
+        options
+            .platform
+            .as_deref()
+            .or(emulation.platform.as_deref()),
+    )?;
+    if let Some(ref cartridge) = cpu.cartridge {
+        // cartridges bring Octo's settings, which start out as XO-CHIP's
+        eprintln!("Octo cartridge");
+        if profile.platform.is_none() {
+            profile = database.profile(&sha1, Some("xochip"))?;
+        }
+        profile.quirks.apply(&cartridge.quirks());
+        profile.instructions_per_frame = cartridge.tickrate.or(profile.instructions_per_frame);
+        if let Some(palette) = cartridge.palette("cartridge")? {
+            profile.palette = Some(palette);
+        }
+    } else if profile.platform.is_none() {
+        // not in the database, so guess from the code
+        let detection = detect(cpu.program());
+        eprintln!("Unknown rom, running it as {}:", detection.platform);
+        for reason in &detection.reasons {
+            eprintln!("  {}", reason);
+        }
+        profile = database.profile(&sha1, Some(detection.platform))?;
+        profile.quirks.apply(&detection.quirks);
+    }
+    cpu.quirks = profile.quirks;
+    cpu.quirks.apply(&emulation.quirks);
+
+    // the first load only knew the settings by file name; now a load address set for the
+    // rom's sha1 or by its platform (the ETI-660 loads elsewhere) can move it, unless the
+    // rom file said where it goes
+    let address = options
+        .load_address
+        .or(emulation.load_address)
+        .or(profile.load_address);
+    if let (Some(address), None) = (address, cpu.rom_origin()) {
+        if address as usize != cpu.program_address() {
+            if let Err(e) = cpu.relocate(address) {
+                eprintln!("Failed to load rom: {}", e);
+                return Ok(());
+            }
+        }
+    }
+    // settings for the rom's sha1 and its platform can still change the fonts
+    let font = options.font.or(emulation.font).or(profile.font);
+    let font_address = options
+        .font_address
+        .or(emulation.font_address)
+        .or(profile.font_address);
+    if font.is_some() || font_address.is_some() {
+        cpu.set_font(font.unwrap_or_default(), font_address.unwrap_or(0))?;
+    }
+    if let Some((width, height)) = profile.resolution {
+        cpu.set_screen_size(width, height);
+    }
+    let instructions_per_frame = options
+        .ipf
+        .or(emulation.instructions_per_frame)
+        .or(profile.instructions_per_frame)
+        .unwrap_or(INSTRUCTIONS_PER_FRAME);
+    let timing = options
+        .timing
+        .or(emulation.timing)
+        .unwrap_or(Timing::Instructions);
+
+    // hybrid VIP programs call 1802 machine code, which needs the VIP's processor
+    let cdp1802 = options.cdp1802.or(emulation.cdp1802).or_else(|| {
+        (profile.platform.as_deref() == Some("hybridVIP")).then_some(Cdp1802Mode::Routines)
+    });
+    if let Some(mode) = cdp1802 {
+        let read = |filename: Option<&String>| match filename {
+            Some(filename) => {
+                fs::read(filename).map_err(|e| format!("Couldn't read {}: {}", filename, e))
+            }
+            None => Ok(vec![]),
+        };
+        let monitor = read(
+            options
+                .vip_monitor
+                .as_ref()
+                .or(emulation.vip_monitor.as_ref()),
+        )?;
+        let interpreter = read(
+            options
+                .vip_interpreter
+                .as_ref()
+                .or(emulation.vip_interpreter.as_ref()),
+        )?;
+        if mode == Cdp1802Mode::Interpreter {
+            if interpreter.is_empty() || monitor.is_empty() {
+                return Err("Running a VIP interpreter needs its image and the monitor ROM".into());
+            }
+            if interpreter.len() > cpu.program_address() {
+                return Err("The VIP interpreter image runs into the program".into());
+            }
+        }
+        cpu.set_vip(Vip::new(mode, monitor), &interpreter);
+    }
+    if let Some(engine) = options.engine.or(emulation.engine) {
+        cpu.set_engine(engine);
+    }
+
+    let mut keys = config.keys_for(&options.rom, &sha1);
+    if options.layout.is_some() {
+        keys.layout = options.layout.clone();
+    }
+    let bindings = KeyBindings::from_config(&keys)?;
+    let host_keys = host_keys(&bindings)?;
+
+    if let Some(ref title) = profile.title {
+        if profile.authors.is_empty() {
+            eprintln!("{}", title);
+        } else {
+            eprintln!("{} by {}", title, profile.authors.join(", "));
+        }
+    }
+    if let Some(platform) = profile
+        .platform
+        .as_deref()
+        .and_then(|id| database.platform(id))
+    {
+        eprintln!("Platform: {}", platform.name);
+    }
+    for (action, key) in &profile.keys {
+        eprintln!(
+            "  {}: {:X} ({})",
+            action,
+            key,
+            bindings.names_for(*key).join(", ")
+        );
+    }
+    cpu.keypad.set_bindings(bindings);
+
+    let controller = config.controller_for(&options.rom, &sha1);
+    let controller = ControllerBindings::from_config(&controller)?;
+    check_controls(&controller)?;
+    cpu.keypad.set_controller_bindings(controller);
+    let waveform = Waveform::from_name(&config.audio.waveform)
+        .ok_or(format!("Unknown waveform: {}", config.audio.waveform))?;
+
+    let mut palettes = Palette::builtin();
+    for filename in &config.display.palettes {
+        palettes.push(Palette::load(filename)?);
+    }
+    let mut palette = match options.palette.as_ref().or(config.display.palette.as_ref()) {
+        Some(name) => Palette::select(&mut palettes, name)?,
+        None => match profile.palette {
+            // the rom's own colors, unless the user picked a palette
+            Some(ref colors) => {
+                palettes.push(colors.clone());
+                palettes.len() - 1
+            }
+            None => 0,
+        },
+    };
+    if let Some(ref color) = config.display.background {
+        palettes[palette].colors[0] = parse_color(color)?;
+    }
+    if let Some(ref color) = config.display.foreground {
+        palettes[palette].colors[1] = parse_color(color)?;
+    }
+
+    let persistence = options.persistence.unwrap_or(config.display.persistence);
+    let scale = options
+        .scale
+        .or(config.display.scale)
+        .unwrap_or(DISPLAY_MODIFIER)
+        .max(1);
+    let integer_scale = options.integer_scale || config.display.integer_scaling;
+    let fullscreen = options.fullscreen || config.display.fullscreen;
+    let mut filters = Filters::from_names(&config.display.filters)?;
+    for name in &options.filters {
+        filters.enable(name)?;
+    }
+    let screenshot_dir = options
+        .screenshot_dir
+        .clone()
+        .unwrap_or_else(|| config.screenshot.directory.clone());
+    let screenshot_scale = options
+        .screenshot_scale
+        .unwrap_or(config.screenshot.scale)
+        .max(1);
+    let record_scale = options
+        .record_scale
+        .unwrap_or(config.recording.scale)
+        .max(1);
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let audio_subsystem = sdl_context.audio().unwrap();
-    let window = video_subsystem
-        .window("Chip 8 Emu", DISPLAY_WIDTH, DISPLAY_HEIGHT)
-        .position_centered()
-        .build()
-        .map_err(|e| e.to_string())
-        .unwrap();
+    let controller_subsystem = sdl_context.game_controller().unwrap();
+    let (width, height) = cpu.resolution();
+    let mut display = Display::new(width, height, persistence);
+    let title = profile.title.as_deref().unwrap_or("Chip 8 Emu");
+    let mut window = video_subsystem.window(title, width as u32 * scale, height as u32 * scale);
+    window.position_centered().resizable();
+    if fullscreen {
+        window.fullscreen_desktop();
+    }
+    let window = window.build().map_err(|e| e.to_string()).unwrap();
     let mut canvas = window
         .into_canvas()
         .build()
         .map_err(|e| e.to_string())
         .unwrap();
     let texture_creator = canvas.texture_creator();
+    let mut filter_scale = filters.scale(scale as usize);
+    let mut texture_size = (width * filter_scale, height * filter_scale);
     let mut texture = texture_creator
-        .create_texture_streaming(PixelFormatEnum::RGB24, SCREEN_WIDTH, SCREEN_HEIGHT)
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            texture_size.0 as u32,
+            texture_size.1 as u32,
+        )
         .map_err(|e| e.to_string())
         .unwrap();
-    let mut _audio_device = None;
-    let has_sound = path::Path::new("beep.wav").exists();
-    let mut timer = 0;
+    let mut redraw = true;
+
+    // the buzzer runs for the whole session and is only gated on and off by the sound timer
+    let desired_spec = AudioSpecDesired {
+        freq: Some(SAMPLE_RATE),
+        channels: Some(1), // mono
+        samples: None,     // default
+    };
+    let mut audio_device = match audio_subsystem.open_playback(None, &desired_spec, |spec| Sound {
+        tone: Tone::new(
+            waveform,
+            config.audio.frequency,
+            config.audio.volume,
+            spec.freq,
+        ),
+    }) {
+        Ok(device) => {
+            device.resume();
+            Some(device)
+        }
+        Err(e) => {
+            eprintln!("Couldn't open audio device, running without sound: {}", e);
+            None
+        }
+    };
+
+    let mut wav_writer = match options.record_audio {
+        Some(ref filename) => {
+            let tone = Tone::new(
+                waveform,
+                config.audio.frequency,
+                config.audio.volume,
+                SAMPLE_RATE,
+            );
+            Some(WavWriter::create(filename, tone, SAMPLE_RATE as u32)?)
+        }
+        None => None,
+    };
+
+    // opened controllers have to be kept alive to keep receiving their events
+    let mut controllers = vec![];
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let frame = Duration::from_secs(1) / FRAME_RATE;
+    let mut next_frame = Instant::now();
+    let mut frames: u64 = 0;
+    let mut recorder: Option<Recorder> = None;
 
     'mainloop: loop {
-        for event in sdl_context.event_pump().unwrap().poll_iter() {
+        for event in event_pump.poll_iter() {
             match event {
                 Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
                 }
                 | Event::Quit { .. } => break 'mainloop,
-                Event::KeyDown { keycode, .. } => cpu.keypad.press(keycode, 1),
-                Event::KeyUp { keycode, .. } => cpu.keypad.press(keycode, 0),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    ..
+                } => {
+                    palette = (palette + 1) % palettes.len();
+                    eprintln!("Palette: {}", palettes[palette].name);
+                    cpu.gfx.mark_dirty();
+                    cpu.draw_flag = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    ..
+                } => {
+                    let window = canvas.window_mut();
+                    let fullscreen = match window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off,
+                    };
+                    if let Err(e) = window.set_fullscreen(fullscreen) {
+                        eprintln!("Couldn't switch fullscreen: {}", e);
+                    }
+                    redraw = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    keymod,
+                    ..
+                } => {
+                    // shift saves at the size the screen is shown at instead
+                    let scale = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        let (_, _, w, _) = letterbox(
+                            canvas.output_size()?,
+                            (display.width as u32, display.height as u32),
+                            integer_scale,
+                        );
+                        (w as usize / display.width).max(1)
+                    } else {
+                        screenshot_scale
+                    };
+                    match save_screenshot(&display, scale, &screenshot_dir, &options.rom) {
+                        Ok(filename) => eprintln!("Saved screenshot {}", filename.display()),
+                        Err(e) => eprintln!("Couldn't save screenshot: {}", e),
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(key @ Keycode::F5),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(key @ Keycode::F6),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(key @ Keycode::F7),
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(key @ Keycode::F8),
+                    ..
+                } => {
+                    let name = match key {
+                        Keycode::F5 => "scanlines",
+                        Keycode::F6 => "grid",
+                        Keycode::F7 => "bloom",
+                        _ => "smooth",
+                    };
+                    match filters.toggle(name) {
+                        Ok(on) => eprintln!("Filter {}: {}", name, if on { "on" } else { "off" }),
+                        Err(e) => eprintln!("Couldn't toggle filter {}: {}", name, e),
+                    }
+                    cpu.gfx.mark_dirty();
+                    cpu.draw_flag = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    ..
+                } => match recorder.take() {
+                    Some(recorder) => match recorder.finish() {
+                        Ok(()) => eprintln!("Stopped recording"),
+                        Err(e) => eprintln!("Couldn't finish recording: {}", e),
+                    },
+                    None => {
+                        let started = timestamped_path(
+                            &config.recording.directory,
+                            &options.rom,
+                            &config.recording.format,
+                        )
+                        .and_then(|filename| {
+                            let filename = filename.to_string_lossy().into_owned();
+                            Recorder::create(
+                                &filename,
+                                display.width * record_scale,
+                                display.height * record_scale,
+                            )
+                            .map(|recorder| (recorder, filename))
+                        });
+                        match started {
+                            Ok((started, filename)) => {
+                                recorder = Some(started);
+                                eprintln!("Recording to {}", filename);
+                            }
+                            Err(e) => eprintln!("Couldn't start recording: {}", e),
+                        }
+                    }
+                },
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                }
+                | Event::Window {
+                    win_event: WindowEvent::Exposed,
+                    ..
+                } => redraw = true,
+                Event::KeyDown {
+                    keycode, scancode, ..
+                } => press_keys(&mut cpu, &host_keys, keycode, scancode, 1),
+                Event::KeyUp {
+                    keycode, scancode, ..
+                } => press_keys(&mut cpu, &host_keys, keycode, scancode, 0),
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match controller_subsystem.open(which) {
+                        Ok(controller) => controllers.push(controller),
+                        Err(e) => eprintln!("Couldn't open controller {}: {}", which, e),
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.retain(|controller| controller.instance_id() != which);
+                }
+                Event::ControllerButtonDown { button, .. } => {
+                    cpu.keypad.press_button(&button.string(), 1)
+                }
+                Event::ControllerButtonUp { button, .. } => {
+                    cpu.keypad.press_button(&button.string(), 0)
+                }
+                Event::ControllerAxisMotion { axis, value, .. } => {
+                    cpu.keypad.move_axis(&axis.string(), value)
+                }
                 _ => {}
             }
         }
 
-        if timer == 2000 {
-            cpu.opcode();
-            timer = 0;
-        } else {
-            timer += 1;
-        }
-
-        if cpu.draw_flag {
-            // Update texture
-            texture
-                .with_lock(None, |buffer: &mut [u8], pitch: usize| {
-                    for y in 0..SCREEN_HEIGHT as usize {
-                        for x in 0..SCREEN_WIDTH as usize {
-                            let offset: usize = y * pitch + x * 3;
-                            let mut color: u8 = 0;
-                            if cpu.gfx[((y * SCREEN_WIDTH as usize) + x) as usize] != 0 {
-                                color = 255;
-                            }
-                            buffer[offset] = color;
-                            buffer[offset + 1] = color;
-                            buffer[offset + 2] = color;
+        match timing {
+            _ if cpu.runs_vip_interpreter() => cpu.run_vip_interpreter_frame(),
+            Timing::Instructions => cpu.run(instructions_per_frame),
+            Timing::Vip => cpu.run_vip_frame(),
+        }
+        cpu.tick_timers();
+
+        // start over when the program switches resolution
+        let (width, height) = cpu.resolution();
+        if (width, height) != (display.width, display.height) {
+            display.resize(width, height);
+            cpu.gfx.mark_dirty();
+            cpu.draw_flag = true;
+        }
+
+        // filters render at the integer scale the screen is shown at
+        let (_, _, shown, _) = letterbox(
+            canvas.output_size()?,
+            (display.width as u32, display.height as u32),
+            true,
+        );
+        filter_scale = filters.scale(shown as usize / display.width);
+        let size = (display.width * filter_scale, display.height * filter_scale);
+        if size != texture_size {
+            texture = texture_creator
+                .create_texture_streaming(PixelFormatEnum::RGB24, size.0 as u32, size.1 as u32)
+                .map_err(|e| e.to_string())?;
+            texture_size = size;
+            cpu.gfx.mark_dirty();
+            cpu.draw_flag = true;
+        }
+
+        if cpu.draw_flag || display.fading() {
+            let changed = display.render(&cpu.gfx, &palettes[palette]);
+            cpu.gfx.mark_clean();
+
+            if filter_scale > 1 {
+                // filters look at neighbouring pixels, so they run over the whole frame
+                let rgb = filters.apply(&display.rgb, display.width, display.height, filter_scale);
+                texture
+                    .with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                        let row = texture_size.0 * 3;
+                        for y in 0..texture_size.1 {
+                            buffer[y * pitch..y * pitch + row]
+                                .copy_from_slice(&rgb[y * row..(y + 1) * row]);
                         }
-                    }
-                })
-                .unwrap();
+                    })
+                    .unwrap();
+            } else if let Some((x, y, w, h)) = changed {
+                // only the rectangle that changed goes to the texture
+                let start = (y * display.width + x) * 3;
+                texture
+                    .update(
+                        Rect::new(x as i32, y as i32, w as u32, h as u32),
+                        &display.rgb[start..],
+                        display.width * 3,
+                    )
+                    .map_err(|e| e.to_string())?;
+            }
 
+            cpu.draw_flag = false;
+            redraw = true;
+        }
+
+        if redraw {
+            // the border around the letterboxed screen takes the background color
+            let [r, g, b] = palettes[palette].color(0);
+            canvas.set_draw_color(Color::RGB(r, g, b));
             canvas.clear();
 
             // Copy over new texture to canvas
-            canvas
-                .copy(
-                    &texture,
-                    None,
-                    Some(Rect::new(0, 0, DISPLAY_WIDTH, DISPLAY_HEIGHT)),
-                )
-                .unwrap();
+            let (x, y, w, h) = letterbox(
+                canvas.output_size()?,
+                (display.width as u32, display.height as u32),
+                integer_scale,
+            );
+            canvas.copy(&texture, None, Some(Rect::new(x, y, w, h)))?;
 
             // display new changes to canvas
             canvas.present();
 
-            cpu.draw_flag = false;
+            redraw = false;
         }
 
-        if cpu.beep_flag {
-            if has_sound {
-                let desired_spec = AudioSpecDesired {
-                    freq: Some(44_100),
-                    channels: Some(1), // mono
-                    samples: None,     // default
-                };
-
-                _audio_device = Some(Box::new(
-                    audio_subsystem
-                        .open_playback(None, &desired_spec, |spec| {
-                            let wav = AudioSpecWAV::load_wav("beep.wav")
-                                .expect("could not load test WAV file");
-                            let cvt = AudioCVT::new(
-                                wav.format,
-                                wav.channels,
-                                wav.freq,
-                                spec.format,
-                                spec.channels,
-                                spec.freq,
-                            )
-                            .expect("could not convert WAV file");
-                            let data = cvt.convert(wav.buffer().to_vec());
-
-                            Sound {
-                                data,
-                                volume: 0.25,
-                                pos: 0,
-                            }
-                        })
-                        .unwrap(),
-                ));
+        frames += 1;
+        if options.screenshot_after == Some(frames) {
+            match save_screenshot(&display, screenshot_scale, &screenshot_dir, &options.rom) {
+                Ok(filename) => eprintln!("Saved screenshot {}", filename.display()),
+                Err(e) => eprintln!("Couldn't save screenshot: {}", e),
+            }
+        }
 
-                // start playback
-                if let Some(ref dev) = _audio_device {
-                    dev.resume();
+        if let Some(ref filename) = options.record {
+            if frames == options.record_start + 1 {
+                // a recording started by hand gives way to the one asked for on the command line
+                if let Some(recorder) = recorder.take() {
+                    if let Err(e) = recorder.finish() {
+                        eprintln!("Couldn't finish recording: {}", e);
+                    }
                 }
-            } else {
-                println!("BEEP");
+                match Recorder::create(
+                    filename,
+                    display.width * record_scale,
+                    display.height * record_scale,
+                ) {
+                    Ok(started) => {
+                        recorder = Some(started);
+                        eprintln!("Recording to {}", filename);
+                    }
+                    Err(e) => eprintln!("Couldn't start recording: {}", e),
+                }
+            }
+        }
+        if let Some(ref mut recorder) = recorder {
+            recorder.write_frame(&display)?;
+        }
+        if options.record_stop == Some(frames) {
+            if let Some(recorder) = recorder.take() {
+                recorder.finish()?;
+                eprintln!("Stopped recording");
             }
+        }
+
+        if let Some(ref mut device) = audio_device {
+            let mut sound = device.lock();
+            sound.tone.gate = cpu.sound_active();
+            sound.tone.pattern = cpu.audio_pattern();
+        }
+        if let Some(ref mut wav) = wav_writer {
+            wav.write_frame(cpu.sound_active(), cpu.audio_pattern())?;
+        }
 
-            cpu.beep_flag = false;
+        // wait out the rest of the 60 Hz frame
+        next_frame += frame;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
         }
     }
 
-    println!("Tearing down emu.");
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if let Some(wav) = wav_writer {
+        wav.finish()?;
+    }
+
+    eprintln!("Tearing down emu.");
     return Ok(());
 }
@@ -0,0 +1,90 @@
+use chip_8_rust::components::constants::INSTRUCTIONS_PER_FRAME;
+use chip_8_rust::components::cpu::Cpu;
+use chip_8_rust::components::recompiler::Engine;
+
+#[path = "../benches/mixes/mod.rs"]
+mod mixes;
+use mixes::MIXES;
+
+// lockstep panics with the first difference between the block engine and the interpreter,
+// so each test passes if the program runs this many frames without one
+const FRAMES: u32 = 300;
+
+// calls two subroutines so their blocks are cached, then rewrites them: FX55 turns 6105 in
+// the first into 6107, FX33 turns the skip in the second into one that is taken over what
+// is left of the BCD (0000, which would panic if it ran). the last V1, 8, is drawn
+#[rustfmt::skip]
+const SELF_MODIFYING: [u8; 54] = [
+    0x22, 0x20, // 200: call 220
+    0x60, 0x61, // 202: V0 = 61
+    0x61, 0x07, // 204: V1 = 07
+    0xA2, 0x20, // 206: I = 220
+    0xF1, 0x55, // 208: store V0-V1 at 220
+    0x61, 0x00, // 20A: V1 = 0
+    0x22, 0x20, // 20C: call 220, V1 = 7
+    0x22, 0x30, // 20E: call 230, V1 = 8
+    0x62, 0xC8, // 210: V2 = 200
+    0xA2, 0x31, // 212: I = 231
+    0xF2, 0x33, // 214: BCD of V2 at 231
+    0x60, 0x02, // 216: V0 = 2
+    0x22, 0x30, // 218: call 230, skipping 7101
+    0xF1, 0x29, // 21A: I = digit V1
+    0xD0, 0x15, // 21C: draw it at (V0, V1)
+    0x12, 0x1E, // 21E: loop
+    0x61, 0x05, // 220: V1 = 5
+    0x00, 0xEE, // 222: return
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x30, 0xFF, // 230: skip if V0 = FF
+    0x71, 0x01, // 232: V1 += 1
+    0x00, 0xEE, // 234: return
+];
+
+fn rom(name: &str) -> Vec<u8> {
+    let path = format!("{}/programs/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Couldn't read {}: {}", path, e))
+}
+
+// runs `program` for `frames` frames the way the main loop does
+fn run(program: &[u8], engine: Engine, frames: u32) -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.load_bytes(program).expect("Failed to load rom");
+    cpu.set_engine(engine);
+    for _ in 0..frames {
+        cpu.run(INSTRUCTIONS_PER_FRAME);
+        cpu.tick_timers();
+    }
+    cpu
+}
+
+#[test]
+fn bundled_roms() {
+    let directory = format!("{}/programs", env!("CARGO_MANIFEST_DIR"));
+    let mut names: Vec<String> = std::fs::read_dir(directory)
+        .expect("Couldn't list programs")
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".c8"))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty());
+    for name in names {
+        run(&rom(&name), Engine::Lockstep, FRAMES);
+    }
+}
+
+#[test]
+fn opcode_mixes() {
+    for (_, program) in MIXES {
+        run(program, Engine::Lockstep, FRAMES);
+    }
+}
+
+#[test]
+fn stores_into_cached_blocks() {
+    run(&SELF_MODIFYING, Engine::Lockstep, FRAMES);
+
+    // a block kept after the write would leave V1 at 5 and then 6, or run the 0000
+    let blocks = run(&SELF_MODIFYING, Engine::Blocks, FRAMES);
+    let interpreter = run(&SELF_MODIFYING, Engine::Interpreter, FRAMES);
+    assert!(blocks.gfx == interpreter.gfx);
+    assert_eq!(blocks.gfx.pixel(2, 8), 1);
+}